@@ -5,9 +5,25 @@ use utils::{get_char_class, CharClass, Position};
 
 use crate::EditorError;
 
+/// How many edit positions the changelist remembers.
+const CHANGELIST_CAP: usize = 100;
+
+/// A single reversible edit applied to the `Buffer`.
+/// Everything needed to undo (and redo) the edit is stored here.
+enum Edit {
+    Insert { char_idx: usize, text: String },
+    Delete { char_idx: usize, text: String },
+    Replace { char_idx: usize, old: String, new: String },
+}
+
 pub struct Buffer {
     text_engine: TextEngine,
     pub file_path: Option<String>, // File associated with `Buffer`.
+    undo_stack: Vec<Edit>,
+    redo_stack: Vec<Edit>,
+    coalesce_inserts: bool, // Whether the next insert may join the previous undo entry.
+    changelist: Vec<Position>, // Where recent edits happened, walked by `;`/`,`.
+    change_index: usize,       // Where in the changelist `;` continues from.
 }
 
 impl Buffer {
@@ -15,6 +31,11 @@ impl Buffer {
         Self {
             text_engine: TextEngine::new(),
             file_path: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            coalesce_inserts: false,
+            changelist: Vec::new(),
+            change_index: 0,
         }
     }
 
@@ -30,9 +51,32 @@ impl Buffer {
         Ok(Buffer {
             text_engine,
             file_path: Some(file_path),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            coalesce_inserts: false,
+            changelist: Vec::new(),
+            change_index: 0,
         })
     }
 
+    /// Writes the buffer contents back to its file.
+    pub fn save(&self) -> Result<(), EditorError> {
+        let path = self.file_path.as_ref().ok_or_else(|| {
+            EditorError::BufferError("Buffer has no file path to save to".to_string())
+        })?;
+
+        self.text_engine
+            .write_to(path)
+            .map_err(|e| EditorError::BufferError(format!("Could not write to {path}: {e}")))
+    }
+
+    /// Writes the buffer contents to a new path, which becomes the
+    /// buffer's file from now on.
+    pub fn save_as(&mut self, path: String) -> Result<(), EditorError> {
+        self.file_path = Some(path);
+        self.save()
+    }
+
     /// Returns a line with removed '\n' and empty lines from the end.
     /// This avoids the issue of not rendering the first character.
     pub fn get_trimmed_line(&self, line_idx: usize) -> RopeSlice {
@@ -59,6 +103,40 @@ impl Buffer {
             .len_chars()
     }
 
+    /// Returns the x of the next grapheme cluster boundary on the
+    /// cursor's line (may equal the line length at the end).
+    pub fn next_grapheme_x(&self, position: Position) -> usize {
+        let char_idx = self.position_to_char_idx(position);
+        let line_start = self.text_engine.line_to_char(position.y);
+
+        self.text_engine
+            .next_grapheme_boundary(char_idx)
+            .saturating_sub(line_start)
+    }
+
+    /// Returns the x of the previous grapheme cluster boundary on the
+    /// cursor's line.
+    pub fn prev_grapheme_x(&self, position: Position) -> usize {
+        let char_idx = self.position_to_char_idx(position);
+        let line_start = self.text_engine.line_to_char(position.y);
+
+        self.text_engine
+            .prev_grapheme_boundary(char_idx)
+            .saturating_sub(line_start)
+    }
+
+    /// Snaps an x that may sit inside a grapheme cluster (e.g. a column
+    /// remembered from another line by vertical motion) back to the
+    /// cluster's start.
+    pub fn snap_to_grapheme_x(&self, position: Position) -> usize {
+        let char_idx = self.position_to_char_idx(position);
+        let line_start = self.text_engine.line_to_char(position.y);
+
+        self.text_engine
+            .grapheme_start(char_idx)
+            .saturating_sub(line_start)
+    }
+
     /// Returns the index of the start of the next word from a given position.
     pub fn find_next_word_start(&self, position: Position, big_word: bool) -> Option<Position> {
         let total_chars = self.text_engine.len_chars();
@@ -207,15 +285,73 @@ impl Buffer {
         Some(self.text_engine.char_idx_to_position(last_char_index))
     }
 
+    //
+    // Searching
+    //
+
+    /// Finds the first match of `pattern` strictly after `position`,
+    /// wrapping around to the start of the buffer.
+    pub fn search_forward(&self, position: Position, pattern: &str) -> Option<Position> {
+        let from = self.position_to_char_idx(position) + 1;
+
+        self.text_engine
+            .search_forward(from, pattern)
+            .or_else(|| self.text_engine.search_forward(0, pattern))
+            .map(|idx| self.text_engine.char_idx_to_position(idx))
+    }
+
+    /// Finds the first match of `pattern` strictly before `position`,
+    /// wrapping around to the end of the buffer.
+    pub fn search_backward(&self, position: Position, pattern: &str) -> Option<Position> {
+        let before = self.position_to_char_idx(position);
+        let total = self.text_engine.len_chars();
+
+        self.text_engine
+            .search_backward(before, pattern)
+            .or_else(|| self.text_engine.search_backward(total, pattern))
+            .map(|idx| self.text_engine.char_idx_to_position(idx))
+    }
+
+    /// Returns the start position and char length of every match of
+    /// `pattern`, for search highlighting.
+    pub fn find_all_positions(&self, pattern: &str) -> Vec<(Position, usize)> {
+        self.text_engine
+            .find_all(pattern)
+            .into_iter()
+            .map(|(start, end)| (self.text_engine.char_idx_to_position(start), end - start))
+            .collect()
+    }
+
+    /// Finds the bracket matching the one at `position`, or `None` when
+    /// the character there is not a bracket or its pair is unbalanced.
+    pub fn find_matching_bracket(&self, position: Position) -> Option<Position> {
+        let char_idx = self.position_to_char_idx(position);
+        let matching = self.text_engine.find_matching_bracket(char_idx)?;
+
+        Some(self.text_engine.char_idx_to_position(matching))
+    }
+
     //
     // Editing
     //
 
     pub fn insert_char(&mut self, position: Position, c: char) {
         let char_idx = self.position_to_char_idx(position);
+        self.record_insert(char_idx, c);
         self.text_engine.insert_char(char_idx, c);
     }
 
+    /// Inserts a whole block of text (e.g. a paste) at a position as one
+    /// undo entry, returning the `Position` just past the inserted text.
+    pub fn insert_text(&mut self, position: Position, text: &str) -> Position {
+        let char_idx = self.position_to_char_idx(position);
+
+        self.record_insert_text(char_idx, text);
+        self.text_engine.insert(char_idx, text);
+        self.text_engine
+            .char_idx_to_position(char_idx + text.chars().count())
+    }
+
     pub fn delete_char_backward(&mut self, position: Position) {
         let char_idx = self.position_to_char_idx(position);
         if char_idx == 0 {
@@ -223,9 +359,48 @@ impl Buffer {
             return;
         }
 
+        self.record_delete(char_idx - 1, self.text_engine.char(char_idx - 1).to_string());
         self.text_engine.delete_char_backward(char_idx);
     }
 
+    /// Deletes every character between two positions (both inclusive),
+    /// like vim's visual mode `d`.
+    pub fn delete_range(&mut self, start: Position, end: Position) {
+        let start_idx = self.position_to_char_idx(start);
+        let end_idx = self.position_to_char_idx(end);
+
+        self.record_delete(
+            start_idx,
+            self.text_engine.slice_to_string(start_idx, end_idx + 1),
+        );
+
+        // The end position is inclusive, so delete one past it.
+        self.text_engine.remove_range(start_idx, end_idx + 1);
+    }
+
+    /// Replaces the text between two positions (both inclusive) with
+    /// `with`, as a single undo entry. A no-op when nothing would change.
+    pub fn replace_range(&mut self, start: Position, end: Position, with: &str) {
+        let start_idx = self.position_to_char_idx(start);
+        let end_idx = self.position_to_char_idx(end) + 1;
+
+        let old = self.text_engine.slice_to_string(start_idx, end_idx);
+        if old == with {
+            return;
+        }
+
+        self.record_replace(start_idx, old, with.to_string());
+        self.text_engine.replace_range(start_idx, end_idx, with);
+    }
+
+    /// Returns the text between two positions (both inclusive) as a `String`.
+    pub fn get_range(&self, start: Position, end: Position) -> String {
+        let start_idx = self.position_to_char_idx(start);
+        let end_idx = self.position_to_char_idx(end);
+
+        self.text_engine.slice_to_string(start_idx, end_idx + 1)
+    }
+
     pub fn delete_char_forward(&mut self, position: Position) {
         let total_chars = self.text_engine.len_chars();
         let char_idx = self.position_to_char_idx(position);
@@ -235,9 +410,179 @@ impl Buffer {
             return;
         }
 
+        self.record_delete(char_idx, self.text_engine.char(char_idx).to_string());
         self.text_engine.delete_char_forward(char_idx);
     }
 
+    //
+    // Undo/Redo
+    //
+
+    /// Undoes the most recent edit, returning the `Position` the cursor
+    /// should move to, or `None` if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<Position> {
+        let edit = self.undo_stack.pop()?;
+        let position = self.apply_inverse(&edit);
+        self.redo_stack.push(edit);
+        self.coalesce_inserts = false;
+
+        Some(position)
+    }
+
+    /// Redoes the most recently undone edit, returning the `Position` the
+    /// cursor should move to, or `None` if there is nothing to redo.
+    pub fn redo(&mut self) -> Option<Position> {
+        let edit = self.redo_stack.pop()?;
+        let position = self.apply(&edit);
+        self.undo_stack.push(edit);
+        self.coalesce_inserts = false;
+
+        Some(position)
+    }
+
+    /// Stops the next insert from joining the previous undo entry, so
+    /// each insert session becomes its own undo group.
+    pub fn break_undo_group(&mut self) {
+        self.coalesce_inserts = false;
+    }
+
+    //
+    // Changelist
+    //
+
+    /// Walks the changelist backward, returning the position of an older
+    /// change, or `None` at the start of the list.
+    pub fn goto_older_change(&mut self) -> Option<Position> {
+        if self.change_index == 0 {
+            return None;
+        }
+
+        self.change_index -= 1;
+        self.changelist.get(self.change_index).copied()
+    }
+
+    /// Walks the changelist forward, returning the position of a newer
+    /// change, or `None` at the end of the list.
+    pub fn goto_newer_change(&mut self) -> Option<Position> {
+        if self.change_index + 1 >= self.changelist.len() {
+            return None;
+        }
+
+        self.change_index += 1;
+        self.changelist.get(self.change_index).copied()
+    }
+
+    /// Remembers where an edit happened so the changelist can revisit
+    /// it. Consecutive edits on the same line collapse into one entry,
+    /// and the oldest entry falls off past `CHANGELIST_CAP`.
+    fn record_change_position(&mut self, char_idx: usize) {
+        let position = self.text_engine.char_idx_to_position(char_idx);
+
+        if let Some(last) = self.changelist.last_mut() {
+            if last.y == position.y {
+                *last = position;
+                self.change_index = self.changelist.len();
+                return;
+            }
+        }
+
+        self.changelist.push(position);
+        if self.changelist.len() > CHANGELIST_CAP {
+            self.changelist.remove(0);
+        }
+        self.change_index = self.changelist.len();
+    }
+
+    /// Records an insertion in the undo stack, coalescing consecutive
+    /// inserts into a single undo group.
+    fn record_insert(&mut self, char_idx: usize, c: char) {
+        self.redo_stack.clear();
+        self.record_change_position(char_idx);
+
+        if self.coalesce_inserts {
+            if let Some(Edit::Insert { char_idx: idx, text }) = self.undo_stack.last_mut() {
+                if *idx + text.chars().count() == char_idx {
+                    text.push(c);
+                    return;
+                }
+            }
+        }
+
+        self.undo_stack.push(Edit::Insert {
+            char_idx,
+            text: c.to_string(),
+        });
+        self.coalesce_inserts = true;
+    }
+
+    /// Records a block insertion in the undo stack as its own entry.
+    fn record_insert_text(&mut self, char_idx: usize, text: &str) {
+        self.redo_stack.clear();
+        self.record_change_position(char_idx);
+        self.undo_stack.push(Edit::Insert {
+            char_idx,
+            text: text.to_string(),
+        });
+        self.coalesce_inserts = false;
+    }
+
+    /// Records a deletion in the undo stack.
+    fn record_delete(&mut self, char_idx: usize, text: String) {
+        self.redo_stack.clear();
+        self.record_change_position(char_idx);
+        self.undo_stack.push(Edit::Delete { char_idx, text });
+        self.coalesce_inserts = false;
+    }
+
+    /// Records a replacement in the undo stack.
+    fn record_replace(&mut self, char_idx: usize, old: String, new: String) {
+        self.redo_stack.clear();
+        self.record_change_position(char_idx);
+        self.undo_stack.push(Edit::Replace { char_idx, old, new });
+        self.coalesce_inserts = false;
+    }
+
+    /// Applies the inverse of an `Edit` to the text engine (undo).
+    fn apply_inverse(&mut self, edit: &Edit) -> Position {
+        match edit {
+            Edit::Insert { char_idx, text } => {
+                self.text_engine
+                    .remove_range(*char_idx, *char_idx + text.chars().count());
+                self.text_engine.char_idx_to_position(*char_idx)
+            }
+            Edit::Delete { char_idx, text } => {
+                self.text_engine.insert(*char_idx, text);
+                self.text_engine.char_idx_to_position(*char_idx)
+            }
+            Edit::Replace { char_idx, old, new } => {
+                self.text_engine
+                    .replace_range(*char_idx, *char_idx + new.chars().count(), old);
+                self.text_engine.char_idx_to_position(*char_idx)
+            }
+        }
+    }
+
+    /// Re-applies an `Edit` to the text engine (redo).
+    fn apply(&mut self, edit: &Edit) -> Position {
+        match edit {
+            Edit::Insert { char_idx, text } => {
+                self.text_engine.insert(*char_idx, text);
+                self.text_engine
+                    .char_idx_to_position(*char_idx + text.chars().count())
+            }
+            Edit::Delete { char_idx, text } => {
+                self.text_engine
+                    .remove_range(*char_idx, *char_idx + text.chars().count());
+                self.text_engine.char_idx_to_position(*char_idx)
+            }
+            Edit::Replace { char_idx, old, new } => {
+                self.text_engine
+                    .replace_range(*char_idx, *char_idx + old.chars().count(), new);
+                self.text_engine.char_idx_to_position(*char_idx)
+            }
+        }
+    }
+
     //
     // Helpers
     //
@@ -252,3 +597,148 @@ impl Buffer {
         line_start_idx + x
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: usize, y: usize) -> Position {
+        Position { x, y }
+    }
+
+    fn line(buffer: &Buffer, y: usize) -> String {
+        buffer.get_trimmed_line(y).to_string()
+    }
+
+    /// Typing a word coalesces into one undo group: a single undo removes
+    /// it and a single redo brings it back.
+    #[test]
+    fn undo_redo_round_trips_a_typed_word() {
+        let mut buffer = Buffer::new();
+        for (i, c) in "word".chars().enumerate() {
+            buffer.insert_char(at(i, 0), c);
+        }
+        assert_eq!(line(&buffer, 0), "word");
+
+        let position = buffer.undo().expect("an edit to undo");
+        assert_eq!(line(&buffer, 0), "");
+        assert_eq!((position.x, position.y), (0, 0));
+
+        buffer.redo().expect("an edit to redo");
+        assert_eq!(line(&buffer, 0), "word");
+        assert!(buffer.redo().is_none());
+    }
+
+    #[test]
+    fn break_undo_group_splits_insert_sessions() {
+        let mut buffer = Buffer::new();
+        buffer.insert_char(at(0, 0), 'a');
+        buffer.break_undo_group();
+        buffer.insert_char(at(1, 0), 'b');
+
+        buffer.undo().expect("an edit to undo");
+        assert_eq!(line(&buffer, 0), "a");
+    }
+
+    #[test]
+    fn new_edit_clears_the_redo_stack() {
+        let mut buffer = Buffer::new();
+        buffer.insert_char(at(0, 0), 'a');
+        buffer.undo();
+        buffer.insert_char(at(0, 0), 'b');
+
+        assert!(buffer.redo().is_none());
+        assert_eq!(line(&buffer, 0), "b");
+    }
+
+    #[test]
+    fn undo_restores_a_deleted_range() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text(at(0, 0), "hello world");
+
+        buffer.delete_range(at(0, 0), at(5, 0));
+        assert_eq!(line(&buffer, 0), "world");
+
+        buffer.undo().expect("an edit to undo");
+        assert_eq!(line(&buffer, 0), "hello world");
+    }
+
+    #[test]
+    fn changelist_walks_back_through_edit_sites() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text(at(0, 0), "one\ntwo\nthree\n");
+        buffer.insert_char(at(0, 1), 'x');
+        buffer.insert_char(at(0, 2), 'y');
+
+        // `;` starts at the newest edit site and walks towards the oldest...
+        assert_eq!(buffer.goto_older_change().expect("an older change").y, 2);
+        assert_eq!(buffer.goto_older_change().expect("an older change").y, 1);
+        assert_eq!(buffer.goto_older_change().expect("an older change").y, 0);
+        assert!(buffer.goto_older_change().is_none());
+
+        // ...and `,` back towards the newest.
+        assert_eq!(buffer.goto_newer_change().expect("a newer change").y, 1);
+        assert_eq!(buffer.goto_newer_change().expect("a newer change").y, 2);
+        assert!(buffer.goto_newer_change().is_none());
+    }
+
+    #[test]
+    fn edits_on_the_same_line_collapse_into_one_entry() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text(at(0, 0), "one\ntwo\n");
+        buffer.insert_char(at(0, 1), 'a');
+        buffer.insert_char(at(1, 1), 'b');
+
+        // Both inserts landed on line 1 and share one entry, so the second
+        // `;` already reaches the insert_text entry on line 0.
+        assert_eq!(buffer.goto_older_change().expect("an older change").y, 1);
+        assert_eq!(buffer.goto_older_change().expect("an older change").y, 0);
+        assert!(buffer.goto_older_change().is_none());
+    }
+
+    #[test]
+    fn find_all_positions_reports_every_match() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text(at(0, 0), "ababab");
+
+        let matches = buffer.find_all_positions("ab");
+        let ranges: Vec<(usize, usize, usize)> = matches
+            .iter()
+            .map(|(position, len)| (position.x, position.y, *len))
+            .collect();
+
+        assert_eq!(ranges, vec![(0, 0, 2), (2, 0, 2), (4, 0, 2)]);
+        assert!(buffer.find_all_positions("missing").is_empty());
+    }
+
+    #[test]
+    fn search_forward_wraps_past_the_last_match() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text(at(0, 0), "foo\nbar\nfoo\n");
+
+        // From before the last match, `n` finds it...
+        let hit = buffer.search_forward(at(0, 1), "foo").expect("a match");
+        assert_eq!((hit.x, hit.y), (0, 2));
+
+        // ...and from on it, the search wraps back to the first one.
+        let hit = buffer.search_forward(at(0, 2), "foo").expect("a match");
+        assert_eq!((hit.x, hit.y), (0, 0));
+
+        assert!(buffer.search_forward(at(0, 0), "missing").is_none());
+    }
+
+    #[test]
+    fn search_backward_wraps_past_the_first_match() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text(at(0, 0), "x foo\nbar\nfoo\n");
+
+        let hit = buffer.search_backward(at(0, 2), "foo").expect("a match");
+        assert_eq!((hit.x, hit.y), (2, 0));
+
+        // From the first match, `N` wraps around to the last one.
+        let hit = buffer.search_backward(at(2, 0), "foo").expect("a match");
+        assert_eq!((hit.x, hit.y), (0, 2));
+
+        assert!(buffer.search_backward(at(0, 0), "missing").is_none());
+    }
+}
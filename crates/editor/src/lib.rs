@@ -1,23 +1,280 @@
-use events::{Event, EventHandler};
+use std::collections::{HashMap, HashSet};
+
+use events::{Event, EventHandler, EventSource};
 use movement::{
     move_cursor_after_insert, move_cursor_before_deleting_backward, move_cursor_down,
     move_cursor_end_of_line, move_cursor_first_char_of_line, move_cursor_left, move_cursor_right,
     move_cursor_start_of_line, move_cursor_up, move_cursor_word_backward, move_cursor_word_forward,
     move_cursor_word_forward_end,
 };
-use renderer::{
-    terminal::{Terminal, TerminalInterface},
-    Component, Renderer,
-};
+use renderer::{terminal::TerminalInterface, Component, Renderer};
+use message_bar::{MessageBar, Severity};
+use scrollbar::Scrollbar;
 use status_bar::StatusBar;
 use thiserror::Error;
-use utils::{Command, Mode, Size};
+use utils::{error, info, transform_case, CaseTransform, Command, Mode, Position, Size};
 use window::Window;
 mod buffer;
+mod message_bar;
 mod movement;
+mod scrollbar;
 mod status_bar;
 mod window;
 
+/// One level of indentation, used by `>` and `<`.
+const INDENT_UNIT: &str = "    ";
+
+/// How many positions the jumplist remembers.
+const JUMPLIST_CAP: usize = 100;
+
+/// Removes at most one indent level from the front of a line: a single
+/// tab counts as a full level, otherwise up to `INDENT_UNIT` spaces go.
+fn outdent_line(line: &str) -> String {
+    if let Some(rest) = line.strip_prefix('\t') {
+        return rest.to_string();
+    }
+
+    let spaces = line
+        .chars()
+        .take_while(|c| *c == ' ')
+        .count()
+        .min(INDENT_UNIT.len());
+    line[spaces..].to_string()
+}
+
+/// The closing character that pairs with an opening one, for auto-pairs
+/// and surround. Quotes pair with themselves.
+fn closing_pair(open: char) -> Option<char> {
+    match open {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        _ => None,
+    }
+}
+
+/// Adds `delta` to an integer token, saturating at the `i64` ends.
+/// `007` + 1 stays `008`: however many digits the token had are kept.
+/// `None` when the token does not parse as an integer.
+fn incremented(token: &str, delta: i64) -> Option<String> {
+    let value = token.parse::<i64>().ok()?;
+    let new_value = value.saturating_add(delta);
+
+    let digits = token.chars().filter(|c| c.is_ascii_digit()).count();
+    let sign = if new_value < 0 { "-" } else { "" };
+
+    // `unsigned_abs`, since `i64::MIN.abs()` overflows.
+    Some(format!("{sign}{:0digits$}", new_value.unsigned_abs()))
+}
+
+/// Stable-sorts lines, optionally case-insensitively, optionally in
+/// reverse.
+fn sorted_lines(mut lines: Vec<String>, reverse: bool, case_insensitive: bool) -> Vec<String> {
+    if case_insensitive {
+        lines.sort_by_key(|line| line.to_lowercase());
+    } else {
+        lines.sort();
+    }
+
+    if reverse {
+        lines.reverse();
+    }
+
+    lines
+}
+
+/// The list marker opening a line (`- `, `* `, `+ `, or `12. `), if any,
+/// including its trailing space.
+fn list_marker(text: &str) -> Option<String> {
+    if text.starts_with("- ") || text.starts_with("* ") || text.starts_with("+ ") {
+        return Some(text[..2].to_string());
+    }
+
+    let digits = text.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && text[digits..].starts_with(". ") {
+        return Some(text[..digits + 2].to_string());
+    }
+
+    None
+}
+
+/// Hard-wraps a paragraph's lines to `width` columns at word boundaries,
+/// preserving leading indentation. A line opening with a list marker
+/// starts a new wrap unit whose continuation lines align under the
+/// marker; a token longer than the width is left unbroken on its own
+/// line.
+fn reflow_lines(lines: &[String], width: usize) -> Vec<String> {
+    // (first-line prefix, continuation prefix, words) per wrap unit.
+    let mut units: Vec<(String, String, Vec<String>)> = Vec::new();
+
+    for line in lines {
+        let indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+        let rest = &line[indent.len()..];
+
+        if let Some(marker) = list_marker(rest) {
+            let words = rest[marker.len()..]
+                .split_whitespace()
+                .map(str::to_string)
+                .collect();
+            let continuation = format!("{indent}{}", " ".repeat(marker.chars().count()));
+            units.push((format!("{indent}{marker}"), continuation, words));
+        } else if let Some(unit) = units.last_mut() {
+            unit.2.extend(rest.split_whitespace().map(str::to_string));
+        } else {
+            units.push((
+                indent.clone(),
+                indent,
+                rest.split_whitespace().map(str::to_string).collect(),
+            ));
+        }
+    }
+
+    let mut result = Vec::new();
+    for (first, continuation, words) in units {
+        let mut current = first;
+        let mut has_word = false;
+
+        for word in words {
+            let candidate = current.chars().count() + 1 + word.chars().count();
+            if has_word && candidate > width {
+                result.push(current);
+                current = continuation.clone();
+                current.push_str(&word);
+            } else {
+                if has_word {
+                    current.push(' ');
+                }
+                current.push_str(&word);
+            }
+            has_word = true;
+        }
+
+        result.push(current);
+    }
+
+    result
+}
+
+/// Folds one more digit into a pending count prefix. `None` means the
+/// digit was a leading `0`, which is the start-of-line motion instead.
+fn push_count_digit(pending: Option<usize>, digit: usize) -> Option<usize> {
+    if digit == 0 && pending.is_none() {
+        return None;
+    }
+
+    Some(
+        pending
+            .unwrap_or(0)
+            .saturating_mul(10)
+            .saturating_add(digit),
+    )
+}
+
+/// The count a motion should run with: the pending prefix, or `default`
+/// when none was typed.
+fn resolve_count(pending: Option<usize>, default: usize) -> usize {
+    pending.unwrap_or(default)
+}
+
+/// Expands command-line aliases on the first word, carrying arguments
+/// over, until a non-alias is reached. `Err` carries the name of the
+/// alias that closed a loop.
+fn expand_alias(aliases: &HashMap<String, String>, input: String) -> Result<String, String> {
+    let mut seen: Vec<String> = Vec::new();
+    let mut current = input;
+
+    loop {
+        let (name, args) = match current.split_once(' ') {
+            Some((name, args)) => (name.to_string(), Some(args.to_string())),
+            None => (current.clone(), None),
+        };
+
+        let Some(target) = aliases.get(&name) else {
+            return Ok(current);
+        };
+
+        if seen.contains(&name) {
+            return Err(name);
+        }
+        seen.push(name);
+
+        current = match args {
+            Some(args) => format!("{target} {args}"),
+            None => target.clone(),
+        };
+    }
+}
+
+/// What a command-line input asks the editor to do, decoupled from the
+/// state that carries it out so the parsing stays testable.
+#[derive(Debug, PartialEq, Eq)]
+enum CommandLineAction {
+    Quit,
+    Save,
+    SaveAs(String),
+    SaveAndQuit,
+    Sort {
+        reverse: bool,
+        case_insensitive: bool,
+    },
+    Unique,
+    ClearHighlights,
+    Reflow(usize),
+    GotoLine(usize),
+}
+
+/// Parses a trimmed, alias-expanded command-line input. `None` means the
+/// input is not an editor command.
+fn parse_command_line(input: &str) -> Option<CommandLineAction> {
+    match input {
+        "q" => return Some(CommandLineAction::Quit),
+        "w" => return Some(CommandLineAction::Save),
+        "wq" => return Some(CommandLineAction::SaveAndQuit),
+        "sort" => {
+            return Some(CommandLineAction::Sort {
+                reverse: false,
+                case_insensitive: false,
+            })
+        }
+        "sort!" => {
+            return Some(CommandLineAction::Sort {
+                reverse: true,
+                case_insensitive: false,
+            })
+        }
+        "sort i" => {
+            return Some(CommandLineAction::Sort {
+                reverse: false,
+                case_insensitive: true,
+            })
+        }
+        "sort! i" => {
+            return Some(CommandLineAction::Sort {
+                reverse: true,
+                case_insensitive: true,
+            })
+        }
+        "uniq" => return Some(CommandLineAction::Unique),
+        "nohl" => return Some(CommandLineAction::ClearHighlights),
+        _ => {}
+    }
+
+    if let Some(path) = input.strip_prefix("w ") {
+        return Some(CommandLineAction::SaveAs(path.trim().to_string()));
+    }
+
+    if let Some(width) = input
+        .strip_prefix("reflow ")
+        .and_then(|w| w.trim().parse::<usize>().ok())
+    {
+        return Some(CommandLineAction::Reflow(width));
+    }
+
+    input.parse::<usize>().ok().map(CommandLineAction::GotoLine)
+}
+
 /// Represents all possible errors that can occur in `editor`.
 #[derive(Error, Debug)]
 pub enum EditorError {
@@ -48,7 +305,21 @@ pub struct EditorState<T: TerminalInterface> {
     window: Window, // NOTE: I should probably implement some sort of window manager.
     mode: Mode,
     status_bar: StatusBar,
+    scrollbar: Scrollbar,
+    message_bar: MessageBar,
     renderer: Renderer<T>,
+    register: Option<String>, // Holds the last yanked/deleted text, like vim's unnamed register.
+    search_query: String,     // Text typed so far in the search prompt.
+    search_is_forward: bool,
+    last_search: Option<String>,
+    command_query: String, // Text typed so far in the command line.
+    command_aliases: HashMap<String, String>, // `:x` expanding to `:wq`, and the like.
+    pending_count: Option<usize>, // Count prefix typed before a motion, like the 3 in `3j`.
+    jumplist: Vec<Position>, // Positions before large jumps, walked by `Ctrl-o`/`Ctrl-i`.
+    jump_index: usize,       // Where in the jumplist `Ctrl-o` continues from.
+    last_change: Option<Vec<Command>>, // The last edit sequence, re-applied by `.`.
+    insert_session: Vec<Command>, // Edits recorded while in insert mode.
+    replaying: bool, // Whether `.` is currently re-applying commands.
 }
 
 impl<T> EditorState<T>
@@ -57,20 +328,23 @@ where
 {
     pub fn new(
         event_handler: EventHandler,
-        renderer: Renderer<T>,
+        mut renderer: Renderer<T>,
         file_path: Option<String>,
     ) -> Result<Self, EditorError> {
-        Terminal::init().map_err(|e| {
+        T::init().map_err(|e| {
             EditorError::TerminalError(format!("Could not initialize terminal: {e}"))
         })?;
 
-        let window = Window::from_file(file_path)?;
-        let (width, height) = Terminal::size()
+        let (width, height) = T::size()
             .map_err(|e| EditorError::RenderError(format!("Could not initialize viewport: {e}")))?;
 
         let viewport_size = Size { width, height };
+        let window = Window::from_file(file_path, viewport_size)?;
 
         let status_bar = StatusBar::new(viewport_size);
+        let scrollbar = Scrollbar::new(viewport_size);
+        let message_bar = MessageBar::new(viewport_size);
+        renderer.resize(width, height);
 
         Ok(EditorState {
             should_quit: false,
@@ -78,17 +352,34 @@ where
             window,
             mode: Mode::Normal, // Start with Normal mode.
             status_bar,
+            scrollbar,
+            message_bar,
             renderer,
+            register: None,
+            search_query: String::new(),
+            search_is_forward: true,
+            last_search: None,
+            command_query: String::new(),
+            command_aliases: HashMap::from([("x".to_string(), "wq".to_string())]),
+            pending_count: None,
+            jumplist: Vec::new(),
+            jump_index: 0,
+            last_change: None,
+            insert_session: Vec::new(),
+            replaying: false,
         })
     }
 
     /// Main entrypoint of the application.
-    pub fn run(&mut self) -> Result<(), EditorError> {
+    pub fn run(&mut self) -> Result<(), EditorError>
+    where
+        T: EventSource,
+    {
         loop {
-            // Capture events.
+            // Capture events from the terminal backend.
             let events = self
                 .event_handler
-                .poll_events()
+                .poll_events(self.renderer.terminal_mut())
                 .map_err(|e| EditorError::EventError(format!("Failed to poll events: {e}")))?;
 
             for event in events {
@@ -98,22 +389,11 @@ where
                             Ok(commands) => {
                                 for command in commands {
                                     if let Err(e) = self.apply_command(command) {
-                                        self.renderer.enqueue_command(
-                                            renderer::TerminalCommand::Print(format!(
-                                                "ERROR: {}",
-                                                e
-                                            )),
-                                        );
+                                        self.report_error(format!("{e}"));
                                     }
                                 }
                             }
-                            Err(e) => {
-                                self.renderer
-                                    .enqueue_command(renderer::TerminalCommand::Print(format!(
-                                        "ERROR: {}",
-                                        e
-                                    )));
-                            }
+                            Err(e) => self.report_error(format!("{e}")),
                         }
                     }
                     Event::Resize(width, height) => {
@@ -121,10 +401,31 @@ where
                         let new_size = Size { width, height };
                         self.apply_command(Command::Resize(new_size))?;
                     }
-                    _ => {}
+                    event @ (Event::MouseDown(..)
+                    | Event::MouseUp(..)
+                    | Event::MouseDrag(..)
+                    | Event::Scroll(_)
+                    | Event::Paste(_)) => {
+                        match self.event_handler.handle_event(event, self.mode) {
+                            Ok(commands) => {
+                                for command in commands {
+                                    if let Err(e) = self.apply_command(command) {
+                                        self.report_error(format!("{e}"));
+                                    }
+                                }
+                            }
+                            Err(e) => self.report_error(format!("{e}")),
+                        }
+                    }
+                    Event::Mock => {}
                 }
             }
 
+            // An expired message leaves a stale line on screen.
+            if self.message_bar.expire() {
+                self.window.needs_redraw = true;
+            }
+
             if self.window.needs_redraw {
                 self.render()?;
             }
@@ -137,11 +438,71 @@ where
         Ok(())
     }
 
-    /// Proccess a command and apply it to the editor state.
+    /// Proccess a command and apply it to the editor state, honoring any
+    /// pending count prefix (`3j` runs the motion three times).
     pub fn apply_command(&mut self, command: Command) -> Result<(), EditorError> {
+        match command {
+            Command::CountDigit(digit) => {
+                match push_count_digit(self.pending_count, digit) {
+                    Some(count) => self.pending_count = Some(count),
+                    // A leading `0` is still "start of line".
+                    None => return self.apply_command_once(Command::MoveCursorStartOfLine),
+                }
+
+                Ok(())
+            }
+            Command::GotoLastLine => {
+                // `10G` goes to line 10, plain `G` to the last line.
+                let line = resolve_count(
+                    self.pending_count.take(),
+                    self.window.buffer.len_nonempty_lines(),
+                );
+
+                self.apply_command_once(Command::GotoLine(line))
+            }
+            command if Self::is_motion(&command) => {
+                let count = resolve_count(self.pending_count.take(), 1);
+                for _ in 0..count {
+                    self.apply_command_once(command.clone())?;
+                }
+
+                Ok(())
+            }
+            command => {
+                // Anything that is not a motion discards the pending count.
+                self.pending_count = None;
+                self.apply_command_once(command)
+            }
+        }
+    }
+
+    /// Returns whether a command repeats under a count prefix (`3j`, `3J`).
+    fn is_motion(command: &Command) -> bool {
+        matches!(
+            command,
+            Command::MoveCursorLeft
+                | Command::MoveCursorRight(_)
+                | Command::MoveCursorUp
+                | Command::MoveCursorDown
+                | Command::MoveCursorWordForward(_)
+                | Command::MoveCursorWordBackward(_)
+                | Command::MoveCursorWordForwardEnd(_)
+                | Command::JoinLines
+                | Command::IncrementNumber(_)
+        )
+    }
+
+    /// Applies a single command to the editor state.
+    fn apply_command_once(&mut self, command: Command) -> Result<(), EditorError> {
+        if !self.replaying {
+            self.record_change(&command);
+        }
+
         match command {
             Command::Quit => self.should_quit = true,
-            Command::MoveCursorLeft => move_cursor_left(&mut self.window.cursor),
+            Command::MoveCursorLeft => {
+                move_cursor_left(&mut self.window.cursor, &self.window.buffer)
+            }
             Command::MoveCursorRight(exceed) => {
                 move_cursor_right(&mut self.window.cursor, &self.window.buffer, exceed)
             }
@@ -165,15 +526,13 @@ where
             Command::MoveCursorWordForwardEnd(big_word) => {
                 move_cursor_word_forward_end(&mut self.window.cursor, &self.window.buffer, big_word)
             }
-            Command::None => {}
+            // Count digits and `G` are resolved by `apply_command` before
+            // reaching this point.
+            Command::None | Command::CountDigit(_) | Command::GotoLastLine => {}
             Command::SwitchMode(mode) => self.switch_mode(mode),
             Command::Resize(new_size) => self.handle_resize(new_size)?,
-            Command::InsertChar(c) => {
-                self.window
-                    .buffer
-                    .insert_char(self.window.cursor.position, c);
-                move_cursor_after_insert(&mut self.window.cursor, c)
-            }
+            Command::InsertChar('\n') => self.insert_newline(),
+            Command::InsertChar(c) => self.insert_char(c),
             Command::DeleteCharBackward => {
                 self.window
                     .buffer
@@ -185,6 +544,142 @@ where
                     .buffer
                     .delete_char_forward(self.window.cursor.position);
             }
+            Command::DeleteSelection => self.delete_selection(),
+            Command::YankSelection => self.yank_selection(),
+            Command::Paste(after) => self.paste(after),
+            Command::TransformCase(transform) => self.transform_selection_case(transform),
+            Command::SurroundSelection(open) => self.surround_selection(open),
+            Command::GotoMatchingBracket => self.goto_matching_bracket(),
+            Command::IncrementNumber(delta) => self.increment_number(delta),
+            Command::IndentSelection => self.indent_selection(false),
+            Command::OutdentSelection => self.indent_selection(true),
+            Command::JoinLines => self.join_lines(),
+            Command::OpenLineBelow => self.open_line(true),
+            Command::OpenLineAbove => self.open_line(false),
+            Command::Undo => match self.window.buffer.undo() {
+                Some(position) => self.move_cursor_clamped(position),
+                None => self
+                    .message_bar
+                    .push("Already at oldest change", Severity::Warning),
+            },
+            Command::Redo => match self.window.buffer.redo() {
+                Some(position) => self.move_cursor_clamped(position),
+                None => self
+                    .message_bar
+                    .push("Already at newest change", Severity::Warning),
+            },
+            Command::StartSearch(forward) => {
+                self.search_is_forward = forward;
+                self.search_query.clear();
+                self.switch_mode(Mode::Search);
+            }
+            Command::SearchInput(c) => {
+                self.search_query.push(c);
+                let query = self.search_query.clone();
+                self.update_search_matches(&query);
+            }
+            Command::SearchBackspace => {
+                self.search_query.pop();
+                let query = self.search_query.clone();
+                self.update_search_matches(&query);
+            }
+            Command::SearchCancel => {
+                self.switch_mode(Mode::Normal);
+
+                // Fall back to highlighting the last accepted search.
+                let query = self.last_search.clone().unwrap_or_default();
+                self.update_search_matches(&query);
+            }
+            Command::SearchSubmit => {
+                let query = self.search_query.clone();
+                self.switch_mode(Mode::Normal);
+
+                if !query.is_empty() {
+                    let forward = self.search_is_forward;
+                    self.search(&query, forward);
+                    self.last_search = Some(query);
+                }
+            }
+            Command::SearchForward(query) => {
+                self.search_is_forward = true;
+                self.search(&query, true);
+                self.update_search_matches(&query);
+                self.last_search = Some(query);
+            }
+            Command::SearchBackward(query) => {
+                self.search_is_forward = false;
+                self.search(&query, false);
+                self.update_search_matches(&query);
+                self.last_search = Some(query);
+            }
+            Command::SearchNext => {
+                if let Some(query) = self.last_search.clone() {
+                    let forward = self.search_is_forward;
+                    self.search(&query, forward);
+                }
+            }
+            Command::SearchPrev => {
+                if let Some(query) = self.last_search.clone() {
+                    let forward = self.search_is_forward;
+                    self.search(&query, !forward);
+                }
+            }
+            Command::Save => {
+                self.save_buffer();
+            }
+            Command::SaveAs(path) => {
+                if let Err(e) = self.window.buffer.save_as(path) {
+                    self.report_error(format!("Could not save buffer: {e}"));
+                }
+            }
+            Command::StartCommandLine => {
+                self.command_query.clear();
+                self.switch_mode(Mode::Command);
+            }
+            Command::CommandInput(c) => self.command_query.push(c),
+            Command::CommandBackspace => {
+                self.command_query.pop();
+            }
+            Command::CommandCancel => self.switch_mode(Mode::Normal),
+            Command::CommandSubmit => self.execute_command_line()?,
+            Command::GotoLine(line) => {
+                self.push_jump();
+                self.move_cursor_clamped(Position {
+                    x: 0,
+                    y: line.saturating_sub(1),
+                });
+            }
+            Command::JumpBack => self.jump_back(),
+            Command::JumpForward => self.jump_forward(),
+            Command::GotoLastChange => self.goto_change(false),
+            Command::GotoNextChange => self.goto_change(true),
+            Command::InsertText(text) => {
+                let position = self
+                    .window
+                    .buffer
+                    .insert_text(self.window.cursor.position, &text);
+                self.move_cursor_clamped(position);
+            }
+            Command::SetCursorAtScreen(x, y) => self.set_cursor_at_screen(x, y),
+            Command::ScrollUp(lines) => self.window.scroll_vertically(-(lines as isize)),
+            Command::ScrollDown(lines) => self.window.scroll_vertically(lines as isize),
+            Command::HalfPageDown => self.window.half_page_scroll(true),
+            Command::HalfPageUp => self.window.half_page_scroll(false),
+            Command::PageDown => self.window.page_scroll(true),
+            Command::PageUp => self.window.page_scroll(false),
+            Command::CenterView => self.window.center_view(),
+            Command::ViewTop => self.window.view_top(),
+            Command::ViewBottom => self.window.view_bottom(),
+            Command::RepeatLastChange => {
+                if let Some(sequence) = self.last_change.clone() {
+                    self.replaying = true;
+                    let result = sequence
+                        .into_iter()
+                        .try_for_each(|command| self.apply_command_once(command));
+                    self.replaying = false;
+                    result?;
+                }
+            }
         }
 
         self.window.scroll_to_cursor();
@@ -199,23 +694,726 @@ where
         self.window.scroll_to_cursor();
         self.window.needs_redraw = true;
         self.status_bar.size = new_size;
+        self.scrollbar.size = new_size;
+        self.message_bar.size = new_size;
+        self.renderer.resize(new_size.width, new_size.height);
 
         Ok(())
     }
 
     fn switch_mode(&mut self, mode: Mode) {
-        match mode {
-            Mode::Insert => self
-                .renderer
-                .enqueue_command(renderer::TerminalCommand::ChangeCursorStyleBar),
-            Mode::Normal => self
-                .renderer
-                .enqueue_command(renderer::TerminalCommand::ChangeCursorStyleBlock),
+        let shape = match mode {
+            Mode::Insert | Mode::Search | Mode::Command => renderer::CursorShape::Bar,
+            Mode::Normal | Mode::Visual => renderer::CursorShape::Block,
+        };
+        self.renderer
+            .enqueue_command(renderer::TerminalCommand::SetCursorShape(shape));
+
+        // The selection anchor only lives while in visual mode.
+        self.window.selection_anchor = match mode {
+            Mode::Visual => Some(self.window.cursor.position),
+            _ => None,
+        };
+
+        // Leaving insert mode seals the recorded insert session for `.`.
+        if !self.replaying && matches!(self.mode, Mode::Insert) && !matches!(mode, Mode::Insert) {
+            if !self.insert_session.is_empty() {
+                self.last_change = Some(std::mem::take(&mut self.insert_session));
+            }
+        } else if matches!(mode, Mode::Insert) {
+            self.insert_session.clear();
         }
 
+        // Each insert session forms its own undo group.
+        self.window.buffer.break_undo_group();
+
         self.mode = mode;
     }
 
+    /// Registers a command-line alias, e.g. `x` for `wq`.
+    pub fn add_command_alias(&mut self, alias: impl Into<String>, target: impl Into<String>) {
+        self.command_aliases.insert(alias.into(), target.into());
+    }
+
+    /// Parses and runs whatever was typed in the command line.
+    /// Supports `:q`, `:w`, `:wq`, `:<number>` (go to line) and aliases.
+    fn execute_command_line(&mut self) -> Result<(), EditorError> {
+        let input = self.command_query.trim().to_string();
+        self.switch_mode(Mode::Normal);
+
+        let input = match expand_alias(&self.command_aliases, input) {
+            Ok(input) => input,
+            Err(name) => {
+                self.report_error(format!("Alias loop detected for :{name}"));
+                return Ok(());
+            }
+        };
+
+        let Some(action) = parse_command_line(&input) else {
+            self.report_error(format!("Not an editor command: {input}"));
+            return Ok(());
+        };
+
+        match action {
+            CommandLineAction::Quit => self.apply_command(Command::Quit)?,
+            CommandLineAction::Save => self.apply_command(Command::Save)?,
+            CommandLineAction::SaveAs(path) => self.apply_command(Command::SaveAs(path))?,
+            CommandLineAction::SaveAndQuit => {
+                // A failed write must not quit and drop the buffer.
+                if self.save_buffer() {
+                    self.apply_command(Command::Quit)?;
+                }
+            }
+            CommandLineAction::Sort {
+                reverse,
+                case_insensitive,
+            } => self.sort_lines(reverse, case_insensitive),
+            CommandLineAction::Unique => self.unique_lines(),
+            CommandLineAction::ClearHighlights => self.window.search_matches.clear(),
+            CommandLineAction::Reflow(width) => self.reflow_paragraph(width),
+            CommandLineAction::GotoLine(line) => self.apply_command(Command::GotoLine(line))?,
+        }
+
+        Ok(())
+    }
+
+    /// `:reflow <width>`: rewraps the paragraph under the cursor to the
+    /// given column width, as a single edit.
+    fn reflow_paragraph(&mut self, width: usize) {
+        if width == 0 {
+            self.report_error("reflow needs a width greater than zero".to_string());
+            return;
+        }
+
+        let y = self.window.cursor.position.y;
+        if self.line_is_blank(y) {
+            self.report_error("Nothing to reflow".to_string());
+            return;
+        }
+
+        // Widen to the surrounding paragraph: the run of non-blank lines
+        // around the cursor.
+        let mut first = y;
+        while first > 0 && !self.line_is_blank(first - 1) {
+            first -= 1;
+        }
+        let mut last = y;
+        while last + 1 < self.window.buffer.len_nonempty_lines() && !self.line_is_blank(last + 1) {
+            last += 1;
+        }
+
+        let lines: Vec<String> = (first..=last)
+            .map(|l| self.window.buffer.get_trimmed_line(l).to_string())
+            .collect();
+        let wrapped = reflow_lines(&lines, width);
+
+        let start = Position { x: 0, y: first };
+        let end = Position {
+            x: self.window.buffer.get_line_length(last) - 1,
+            y: last,
+        };
+        self.window
+            .buffer
+            .replace_range(start, end, &wrapped.join("\n"));
+        self.move_cursor_clamped(Position { x: 0, y: first });
+    }
+
+    /// `:sort` / `:sort!` / `:sort i`: sorts the buffer's lines as a
+    /// single edit. `!` reverses the order and `i` ignores case.
+    fn sort_lines(&mut self, reverse: bool, case_insensitive: bool) {
+        let lines = sorted_lines(self.buffer_lines(), reverse, case_insensitive);
+        self.replace_all_lines(lines);
+    }
+
+    /// `:uniq`: drops duplicate lines, keeping each first occurrence.
+    fn unique_lines(&mut self) {
+        let mut seen = HashSet::new();
+        let lines = self
+            .buffer_lines()
+            .into_iter()
+            .filter(|line| seen.insert(line.clone()))
+            .collect();
+
+        self.replace_all_lines(lines);
+    }
+
+    /// Every content line of the buffer, without line breaks.
+    fn buffer_lines(&self) -> Vec<String> {
+        (0..self.window.buffer.len_nonempty_lines())
+            .map(|y| self.window.buffer.get_trimmed_line(y).to_string())
+            .collect()
+    }
+
+    /// Replaces the buffer's content lines as one edit, leaving the
+    /// final line break (if any) where it is.
+    fn replace_all_lines(&mut self, lines: Vec<String>) {
+        let last = self.window.buffer.len_nonempty_lines().saturating_sub(1);
+        let start = Position { x: 0, y: 0 };
+        let end = Position {
+            x: self.window.buffer.get_line_length(last).saturating_sub(1),
+            y: last,
+        };
+
+        self.window.buffer.replace_range(start, end, &lines.join("\n"));
+        self.move_cursor_clamped(self.window.cursor.position);
+    }
+
+    /// Returns whether a line is empty or whitespace-only.
+    fn line_is_blank(&self, y: usize) -> bool {
+        self.window
+            .buffer
+            .get_trimmed_line(y)
+            .chars()
+            .all(|c| c.is_whitespace())
+    }
+
+    /// Saves the buffer to its file, reporting the outcome in the message
+    /// bar. Returns whether the save succeeded, so `:wq` can abort the
+    /// quit instead of discarding an unsaved buffer.
+    fn save_buffer(&mut self) -> bool {
+        match self.window.buffer.save() {
+            Ok(()) => {
+                let name = self
+                    .window
+                    .buffer
+                    .file_path
+                    .clone()
+                    .unwrap_or_else(|| "[No Name]".to_string());
+                info!("Saved {name}");
+                self.message_bar.push(format!("Saved {name}"), Severity::Info);
+                true
+            }
+            Err(e) => {
+                self.report_error(format!("Could not save buffer: {e}"));
+                false
+            }
+        }
+    }
+
+    /// Shows an error in the message bar, also logging it.
+    fn report_error(&mut self, text: String) {
+        error!("{text}");
+        self.message_bar.push(text, Severity::Error);
+        self.window.needs_redraw = true;
+    }
+
+    /// Moves the cursor to the closest match of `query` in the given
+    /// direction, wrapping around the buffer ends.
+    fn search(&mut self, query: &str, forward: bool) {
+        let found = if forward {
+            self.window
+                .buffer
+                .search_forward(self.window.cursor.position, query)
+        } else {
+            self.window
+                .buffer
+                .search_backward(self.window.cursor.position, query)
+        };
+
+        match found {
+            Some(position) => {
+                self.push_jump();
+                self.move_cursor_clamped(position);
+            }
+            None => self.report_error(format!("Pattern not found: {query}")),
+        }
+    }
+
+    /// Recomputes the highlighted search matches for a query, so every
+    /// occurrence lights up (vim's hlsearch). An empty query clears them.
+    fn update_search_matches(&mut self, query: &str) {
+        self.window.search_matches = if query.is_empty() {
+            Vec::new()
+        } else {
+            self.window.buffer.find_all_positions(query)
+        };
+    }
+
+    /// Records the cursor position before a large jump so `Ctrl-o` can
+    /// come back to it. Adjacent duplicates collapse, and the oldest
+    /// entry falls off past `JUMPLIST_CAP`.
+    fn push_jump(&mut self) {
+        let position = self.window.cursor.position;
+
+        // A new jump invalidates whatever `Ctrl-o` had walked back over.
+        self.jumplist.truncate(self.jump_index);
+
+        let duplicate = self
+            .jumplist
+            .last()
+            .is_some_and(|p| p.x == position.x && p.y == position.y);
+        if !duplicate {
+            self.jumplist.push(position);
+            if self.jumplist.len() > JUMPLIST_CAP {
+                self.jumplist.remove(0);
+            }
+        }
+
+        self.jump_index = self.jumplist.len();
+    }
+
+    /// `Ctrl-o`: moves the cursor to the previous jumplist entry.
+    fn jump_back(&mut self) {
+        if self.jump_index == 0 {
+            self.message_bar
+                .push("Already at oldest jump".to_string(), Severity::Warning);
+            return;
+        }
+
+        // Remember where we are so `Ctrl-i` can come back here.
+        if self.jump_index == self.jumplist.len() {
+            let position = self.window.cursor.position;
+            let on_newest = self
+                .jumplist
+                .last()
+                .is_some_and(|p| p.x == position.x && p.y == position.y);
+
+            if on_newest {
+                // Standing on the newest entry: "back" to it would be a
+                // visible no-op, so step straight past it.
+                self.jump_index -= 1;
+                if self.jump_index == 0 {
+                    self.message_bar
+                        .push("Already at oldest jump".to_string(), Severity::Warning);
+                    return;
+                }
+            } else {
+                self.jumplist.push(position);
+            }
+        }
+
+        self.jump_index -= 1;
+        self.move_cursor_clamped(self.jumplist[self.jump_index]);
+    }
+
+    /// `Ctrl-i`: moves the cursor to the next jumplist entry.
+    fn jump_forward(&mut self) {
+        if self.jump_index + 1 >= self.jumplist.len() {
+            self.message_bar
+                .push("Already at newest jump".to_string(), Severity::Warning);
+            return;
+        }
+
+        self.jump_index += 1;
+        self.move_cursor_clamped(self.jumplist[self.jump_index]);
+    }
+
+    /// `;`/`,`: moves the cursor to an older or newer entry in the
+    /// buffer's changelist.
+    fn goto_change(&mut self, newer: bool) {
+        let target = if newer {
+            self.window.buffer.goto_newer_change()
+        } else {
+            self.window.buffer.goto_older_change()
+        };
+
+        match target {
+            Some(position) => self.move_cursor_clamped(position),
+            None => {
+                let text = if newer {
+                    "At end of changelist"
+                } else {
+                    "At start of changelist"
+                };
+                self.message_bar.push(text.to_string(), Severity::Warning);
+            }
+        }
+    }
+
+    /// Records edit commands so `.` can re-apply the last change. While in
+    /// insert mode, edits accumulate into one session that is sealed when
+    /// the mode is left.
+    fn record_change(&mut self, command: &Command) {
+        let is_edit = matches!(
+            command,
+            Command::InsertChar(_)
+                | Command::InsertText(_)
+                | Command::Paste(_)
+                | Command::DeleteCharBackward
+                | Command::DeleteCharForward
+        );
+
+        if !is_edit {
+            return;
+        }
+
+        match self.mode {
+            Mode::Insert => self.insert_session.push(command.clone()),
+            _ => self.last_change = Some(vec![command.clone()]),
+        }
+    }
+
+    /// Moves the cursor to the buffer position under a viewport cell
+    /// (a mouse click). Clicks on the status bar are ignored.
+    fn set_cursor_at_screen(&mut self, x: usize, y: usize) {
+        let content_height = self.window.viewport_size.height.saturating_sub(1);
+        if y >= content_height {
+            return;
+        }
+
+        let scroll = self.window.scroll_offset();
+        self.move_cursor_clamped(Position {
+            x: x + scroll.x,
+            y: y + scroll.y,
+        });
+    }
+
+    /// Moves the cursor to a position, clamping x to the line length.
+    fn move_cursor_clamped(&mut self, position: Position) {
+        self.window.cursor.position.y = position
+            .y
+            .min(self.window.buffer.len_nonempty_lines().saturating_sub(1));
+        self.window.cursor.position.x = position.x.min(
+            self.window
+                .buffer
+                .get_visible_line_length(self.window.cursor.position.y),
+        );
+        self.window.cursor.desired_x = self.window.cursor.position.x;
+    }
+
+    /// Deletes the visual mode selection, saving it in the register.
+    fn delete_selection(&mut self) {
+        if let Some((start, end)) = self.window.selection_range() {
+            self.register = Some(self.window.buffer.get_range(start, end));
+            self.window.buffer.delete_range(start, end);
+
+            // Place the cursor where the selection started, clamped to the
+            // (possibly shorter) line.
+            self.window.cursor.position.y = start.y;
+            self.window.cursor.position.x = start
+                .x
+                .min(self.window.buffer.get_visible_line_length(start.y));
+            self.window.cursor.desired_x = self.window.cursor.position.x;
+        }
+
+        self.switch_mode(Mode::Normal);
+    }
+
+    /// Inserts a typed character, with auto-pairs: an opening pair char
+    /// also inserts its closing half with the cursor between the two, and
+    /// typing a closing char already under the cursor skips over it.
+    fn insert_char(&mut self, c: char) {
+        let position = self.window.cursor.position;
+
+        // Skip over a closing char we inserted earlier instead of
+        // doubling it up.
+        if matches!(c, ')' | ']' | '}' | '"' | '\'')
+            && self
+                .window
+                .buffer
+                .get_trimmed_line(position.y)
+                .chars()
+                .nth(position.x)
+                == Some(c)
+        {
+            move_cursor_after_insert(&mut self.window.cursor, c);
+            return;
+        }
+
+        if let Some(close) = closing_pair(c) {
+            self.window
+                .buffer
+                .insert_text(position, &format!("{c}{close}"));
+            move_cursor_after_insert(&mut self.window.cursor, c);
+            return;
+        }
+
+        self.window.buffer.insert_char(position, c);
+        move_cursor_after_insert(&mut self.window.cursor, c);
+    }
+
+    /// Wraps the visual mode selection in a matching pair, as a single
+    /// edit, like typing `(` with text selected.
+    fn surround_selection(&mut self, open: char) {
+        let Some(close) = closing_pair(open) else {
+            return;
+        };
+
+        if let Some((start, end)) = self.window.selection_range() {
+            let text = self.window.buffer.get_range(start, end);
+            self.window
+                .buffer
+                .replace_range(start, end, &format!("{open}{text}{close}"));
+
+            self.window.cursor.position = start;
+            self.window.cursor.desired_x = start.x;
+        }
+
+        self.switch_mode(Mode::Normal);
+    }
+
+    /// `%`: jumps to the bracket matching the first bracket at or after
+    /// the cursor on the current line. Unbalanced pairs leave the cursor
+    /// where it is.
+    fn goto_matching_bracket(&mut self) {
+        let position = self.window.cursor.position;
+        let line = self.window.buffer.get_trimmed_line(position.y).to_string();
+
+        let Some(offset) = line
+            .chars()
+            .skip(position.x)
+            .position(|c| matches!(c, '(' | ')' | '[' | ']' | '{' | '}'))
+        else {
+            return;
+        };
+
+        let from = Position {
+            x: position.x + offset,
+            y: position.y,
+        };
+        if let Some(target) = self.window.buffer.find_matching_bracket(from) {
+            self.move_cursor_clamped(target);
+        }
+    }
+
+    /// `Ctrl-a`/`Ctrl-x`: adds `delta` to the integer under or after the
+    /// cursor on the current line, preserving leading-zero width and a
+    /// minus sign.
+    fn increment_number(&mut self, delta: i64) {
+        let position = self.window.cursor.position;
+        let chars: Vec<char> = self
+            .window
+            .buffer
+            .get_trimmed_line(position.y)
+            .chars()
+            .collect();
+
+        // The first run of digits that contains or follows the cursor.
+        let mut bounds = None;
+        let mut i = 0;
+        while i < chars.len() {
+            if !chars[i].is_ascii_digit() {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            if i > position.x {
+                bounds = Some((start, i - 1));
+                break;
+            }
+        }
+        let Some((mut start, end)) = bounds else {
+            return;
+        };
+        if start > 0 && chars[start - 1] == '-' {
+            start -= 1;
+        }
+
+        let token: String = chars[start..=end].iter().collect();
+        let Some(replacement) = incremented(&token, delta) else {
+            return;
+        };
+
+        self.window.buffer.replace_range(
+            Position { x: start, y: position.y },
+            Position { x: end, y: position.y },
+            &replacement,
+        );
+
+        // Like vim, the cursor lands on the number's last digit.
+        self.move_cursor_clamped(Position {
+            x: start + replacement.chars().count() - 1,
+            y: position.y,
+        });
+    }
+
+    /// Inserts a line break, auto-indenting the new line: it inherits the
+    /// current line's leading whitespace, plus one extra level when the
+    /// break comes right after an opening brace.
+    fn insert_newline(&mut self) {
+        let position = self.window.cursor.position;
+        let line = self.window.buffer.get_trimmed_line(position.y).to_string();
+
+        let mut indent: String = line
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        if line.chars().take(position.x).last() == Some('{') {
+            indent.push_str(INDENT_UNIT);
+        }
+
+        let end = self
+            .window
+            .buffer
+            .insert_text(position, &format!("\n{indent}"));
+        self.window.cursor.position = end;
+        self.window.cursor.desired_x = end.x;
+    }
+
+    /// Joins the current line with the next, collapsing the next line's
+    /// leading whitespace into a single space, like vim's `J`.
+    fn join_lines(&mut self) {
+        let y = self.window.cursor.position.y;
+        if y + 1 >= self.window.buffer.len_nonempty_lines() {
+            return;
+        }
+
+        let line_len = self.window.buffer.get_line_length(y);
+        let indent = self
+            .window
+            .buffer
+            .get_trimmed_line(y + 1)
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .count();
+
+        // The range covers the line break and the next line's indent.
+        let start = Position { x: line_len, y };
+        let end = if indent == 0 {
+            start
+        } else {
+            Position {
+                x: indent - 1,
+                y: y + 1,
+            }
+        };
+        let with = if line_len == 0 { "" } else { " " };
+
+        self.window.buffer.replace_range(start, end, with);
+        self.move_cursor_clamped(Position { x: line_len, y });
+    }
+
+    /// Opens a new line below or above the cursor, preserving the current
+    /// line's leading indentation, and enters insert mode.
+    fn open_line(&mut self, below: bool) {
+        let y = self.window.cursor.position.y;
+        let indent: String = self
+            .window
+            .buffer
+            .get_trimmed_line(y)
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+
+        if below {
+            let line_len = self.window.buffer.get_line_length(y);
+            let end = self
+                .window
+                .buffer
+                .insert_text(Position { x: line_len, y }, &format!("\n{indent}"));
+            self.window.cursor.position = end;
+            self.window.cursor.desired_x = end.x;
+        } else {
+            self.window
+                .buffer
+                .insert_text(Position { x: 0, y }, &format!("{indent}\n"));
+            self.window.cursor.position = Position {
+                x: indent.chars().count(),
+                y,
+            };
+            self.window.cursor.desired_x = self.window.cursor.position.x;
+        }
+
+        self.switch_mode(Mode::Insert);
+    }
+
+    /// Indents or outdents every line touched by the visual selection by
+    /// one level, as a single edit. Empty lines are left alone; outdent
+    /// removes at most one level.
+    fn indent_selection(&mut self, outdent: bool) {
+        let (first, mut last) = match self.window.selection_range() {
+            Some((start, end)) => (start.y, end.y),
+            None => {
+                self.switch_mode(Mode::Normal);
+                return;
+            }
+        };
+
+        // Empty lines never change, so shrink the span to keep the final
+        // line break out of the replaced range.
+        while last > first && self.window.buffer.get_line_length(last) == 0 {
+            last -= 1;
+        }
+        if self.window.buffer.get_line_length(last) == 0 {
+            self.switch_mode(Mode::Normal);
+            return;
+        }
+
+        let mut lines = Vec::new();
+        for y in first..=last {
+            let line = self.window.buffer.get_trimmed_line(y).to_string();
+            lines.push(if line.is_empty() {
+                line
+            } else if outdent {
+                outdent_line(&line)
+            } else {
+                format!("{INDENT_UNIT}{line}")
+            });
+        }
+
+        let start = Position { x: 0, y: first };
+        let end = Position {
+            x: self.window.buffer.get_line_length(last) - 1,
+            y: last,
+        };
+        self.window.buffer.replace_range(start, end, &lines.join("\n"));
+
+        // Like vim, the cursor lands on the first non-blank of the first
+        // touched line.
+        let x = self
+            .window
+            .buffer
+            .get_trimmed_line(first)
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        self.move_cursor_clamped(Position { x, y: first });
+        self.switch_mode(Mode::Normal);
+    }
+
+    /// Changes the case of the visual mode selection as a single edit.
+    fn transform_selection_case(&mut self, transform: CaseTransform) {
+        if let Some((start, end)) = self.window.selection_range() {
+            let text = self.window.buffer.get_range(start, end);
+            let transformed = transform_case(&text, transform);
+            self.window.buffer.replace_range(start, end, &transformed);
+
+            // Like vim, exit visual mode with the cursor at the start.
+            self.window.cursor.position = start;
+            self.window.cursor.desired_x = start.x;
+        }
+
+        self.switch_mode(Mode::Normal);
+    }
+
+    /// `p`/`P`: inserts the register contents after or before the cursor,
+    /// leaving the cursor on the last pasted character.
+    fn paste(&mut self, after: bool) {
+        let Some(text) = self.register.clone() else {
+            self.message_bar
+                .push("Nothing in register", Severity::Warning);
+            return;
+        };
+
+        let mut position = self.window.cursor.position;
+        if after && self.window.buffer.get_line_length(position.y) > 0 {
+            position.x += 1;
+        }
+
+        let end = self.window.buffer.insert_text(position, &text);
+        self.move_cursor_clamped(Position {
+            x: end.x.saturating_sub(1),
+            y: end.y,
+        });
+    }
+
+    /// Copies the visual mode selection into the register.
+    fn yank_selection(&mut self) {
+        if let Some((start, end)) = self.window.selection_range() {
+            self.register = Some(self.window.buffer.get_range(start, end));
+
+            // Like vim, the cursor jumps to the start of the yanked range.
+            self.window.cursor.position = start;
+            self.window.cursor.desired_x = start.x;
+        }
+
+        self.switch_mode(Mode::Normal);
+    }
+
     fn render(&mut self) -> Result<(), EditorError> {
         let file_name = self.window.buffer.file_path.clone();
         let cursor_position = self.window.cursor.position.clone();
@@ -224,12 +1422,51 @@ where
             .render(&mut self.renderer)
             .map_err(|e| EditorError::RenderError(format!("Could not render status bar: {e}")))?;
 
+        // A live message covers the status line, but never an active prompt.
+        if !matches!(self.mode, Mode::Search | Mode::Command) {
+            self.message_bar
+                .render(&mut self.renderer)
+                .map_err(|e| EditorError::RenderError(format!("Could not render message bar: {e}")))?;
+        }
+
+        let window_was_redrawn = self.window.needs_redraw;
         self.window
             .render(&mut self.renderer)
             .map_err(|e| EditorError::RenderError(format!("Could not render window: {e}")))?;
 
+        if window_was_redrawn {
+            self.scrollbar.update(
+                self.window.scroll_offset().y,
+                self.window.buffer.len_nonempty_lines(),
+            );
+            self.scrollbar
+                .render(&mut self.renderer)
+                .map_err(|e| EditorError::RenderError(format!("Could not render scrollbar: {e}")))?;
+
+            // Drawing the scrollbar moved the terminal cursor, so put it
+            // back on top of the text cursor.
+            let scroll = self.window.scroll_offset();
+            let cursor_x = cursor_position.x.saturating_sub(scroll.x);
+            let cursor_y = cursor_position
+                .y
+                .saturating_sub(scroll.y)
+                .min(self.window.viewport_size.height.saturating_sub(2));
+            self.renderer
+                .enqueue_command(renderer::TerminalCommand::MoveCursor(cursor_x, cursor_y));
+        }
+
+        let prompt = match self.mode {
+            Mode::Search => Some(format!(
+                "{}{}",
+                if self.search_is_forward { '/' } else { '?' },
+                self.search_query
+            )),
+            Mode::Command => Some(format!(":{}", self.command_query)),
+            _ => None,
+        };
+
         self.status_bar
-            .update(self.mode, file_name, cursor_position);
+            .update(self.mode, file_name, cursor_position, prompt);
 
         self.renderer
             .render()
@@ -241,8 +1478,598 @@ where
 
 impl<T: TerminalInterface> Drop for EditorState<T> {
     fn drop(&mut self) {
-        if let Err(_) = Terminal::kill() {
+        if let Err(_) = T::kill() {
             // Do nothing for now.
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::{RendererError, TerminalCommand};
+
+    /// A terminal that accepts everything and draws nothing, so editor
+    /// logic can run headless in tests.
+    struct MockTerminal;
+
+    impl TerminalInterface for MockTerminal {
+        fn init() -> Result<(), RendererError> {
+            Ok(())
+        }
+
+        fn queue(&self, _command: TerminalCommand) -> Result<(), RendererError> {
+            Ok(())
+        }
+
+        fn flush(&self) -> Result<(), RendererError> {
+            Ok(())
+        }
+
+        fn kill() -> Result<(), RendererError> {
+            Ok(())
+        }
+
+        fn size() -> Result<(usize, usize), RendererError> {
+            Ok((80, 24))
+        }
+    }
+
+    /// An editor over a mock terminal, seeded with `text`. The cursor
+    /// starts at (0, 0) in normal mode.
+    fn editor_with(text: &str) -> EditorState<MockTerminal> {
+        let mut state = EditorState::new(
+            EventHandler::new(),
+            Renderer::new(MockTerminal),
+            None,
+        )
+        .expect("a mock editor to build");
+
+        state
+            .window
+            .buffer
+            .insert_text(Position { x: 0, y: 0 }, text);
+        state
+    }
+
+    fn line(state: &EditorState<MockTerminal>, y: usize) -> String {
+        state.window.buffer.get_trimmed_line(y).to_string()
+    }
+
+    fn apply(state: &mut EditorState<MockTerminal>, commands: &[Command]) {
+        for command in commands {
+            state
+                .apply_command(command.clone())
+                .expect("the command to apply");
+        }
+    }
+
+    fn lines(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    #[test]
+    fn list_marker_recognizes_bullets_and_numbers() {
+        assert_eq!(list_marker("- item"), Some("- ".to_string()));
+        assert_eq!(list_marker("* item"), Some("* ".to_string()));
+        assert_eq!(list_marker("12. item"), Some("12. ".to_string()));
+        assert_eq!(list_marker("-item"), None);
+        assert_eq!(list_marker("plain text"), None);
+    }
+
+    #[test]
+    fn reflow_wraps_at_word_boundaries() {
+        assert_eq!(
+            reflow_lines(&lines(&["one two three four"]), 9),
+            lines(&["one two", "three", "four"])
+        );
+    }
+
+    #[test]
+    fn reflow_preserves_indentation() {
+        assert_eq!(
+            reflow_lines(&lines(&["  alpha beta gamma"]), 13),
+            lines(&["  alpha beta", "  gamma"])
+        );
+    }
+
+    #[test]
+    fn reflow_aligns_list_continuations_under_the_marker() {
+        assert_eq!(
+            reflow_lines(&lines(&["- alpha beta gamma"]), 12),
+            lines(&["- alpha beta", "  gamma"])
+        );
+    }
+
+    #[test]
+    fn reflow_keeps_a_too_long_token_unbroken() {
+        assert_eq!(
+            reflow_lines(&lines(&["averyverylongtoken x"]), 5),
+            lines(&["averyverylongtoken", "x"])
+        );
+    }
+
+    #[test]
+    fn sort_is_case_sensitive_by_default() {
+        // Plain `sort` puts all the capitals first, like vim's `:sort`.
+        assert_eq!(
+            sorted_lines(lines(&["banana", "Cherry", "apple"]), false, false),
+            lines(&["Cherry", "apple", "banana"])
+        );
+    }
+
+    #[test]
+    fn case_insensitive_sort_ignores_capitalization() {
+        assert_eq!(
+            sorted_lines(lines(&["banana", "Cherry", "apple"]), false, true),
+            lines(&["apple", "banana", "Cherry"])
+        );
+        // The sort is stable: lines equal under the key keep their order.
+        assert_eq!(
+            sorted_lines(lines(&["B", "b", "a"]), false, true),
+            lines(&["a", "B", "b"])
+        );
+    }
+
+    #[test]
+    fn reverse_sort_flips_the_order() {
+        assert_eq!(
+            sorted_lines(lines(&["a", "c", "b"]), true, false),
+            lines(&["c", "b", "a"])
+        );
+    }
+
+    #[test]
+    fn incremented_adds_and_crosses_zero() {
+        assert_eq!(incremented("9", 1), Some("10".to_string()));
+        assert_eq!(incremented("-1", 1), Some("0".to_string()));
+        assert_eq!(incremented("0", -1), Some("-1".to_string()));
+    }
+
+    #[test]
+    fn incremented_keeps_leading_zeros() {
+        assert_eq!(incremented("007", 1), Some("008".to_string()));
+        assert_eq!(incremented("099", 1), Some("100".to_string()));
+    }
+
+    #[test]
+    fn incremented_saturates_at_the_i64_ends() {
+        let max = i64::MAX.to_string();
+        assert_eq!(incremented(&max, 1), Some(max));
+
+        let min = i64::MIN.to_string();
+        assert_eq!(incremented(&min, -1), Some(min));
+    }
+
+    #[test]
+    fn incremented_rejects_non_numbers() {
+        assert_eq!(incremented("abc", 1), None);
+        assert_eq!(incremented("", 1), None);
+        assert_eq!(incremented("1.5", 1), None);
+    }
+
+    #[test]
+    fn dot_repeats_a_single_delete() {
+        let mut state = editor_with("abc");
+
+        apply(&mut state, &[Command::DeleteCharForward]);
+        assert_eq!(line(&state, 0), "bc");
+
+        // `.` runs the recorded delete again.
+        apply(&mut state, &[Command::RepeatLastChange]);
+        assert_eq!(line(&state, 0), "c");
+    }
+
+    #[test]
+    fn dot_repeats_a_whole_insert_session() {
+        let mut state = editor_with("");
+
+        // Type `hi` in insert mode; leaving the mode seals the session.
+        apply(
+            &mut state,
+            &[
+                Command::SwitchMode(Mode::Insert),
+                Command::InsertChar('h'),
+                Command::InsertChar('i'),
+                Command::SwitchMode(Mode::Normal),
+            ],
+        );
+        assert_eq!(line(&state, 0), "hi");
+
+        apply(&mut state, &[Command::RepeatLastChange]);
+        assert_eq!(line(&state, 0), "hihi");
+    }
+
+    #[test]
+    fn motions_between_edits_do_not_disturb_the_last_change() {
+        let mut state = editor_with("abc\ndef");
+
+        apply(&mut state, &[Command::DeleteCharForward]);
+        apply(
+            &mut state,
+            &[Command::MoveCursorDown, Command::RepeatLastChange],
+        );
+
+        // The delete replays on the line the cursor moved to.
+        assert_eq!(line(&state, 0), "bc");
+        assert_eq!(line(&state, 1), "ef");
+    }
+
+    #[test]
+    fn join_collapses_the_next_lines_indent_to_one_space() {
+        let mut state = editor_with("foo\nbar\n");
+        apply(&mut state, &[Command::JoinLines]);
+        assert_eq!(line(&state, 0), "foo bar");
+        // The cursor sits on the joining space, like vim's `J`.
+        assert_eq!(state.window.cursor.position.x, 3);
+
+        let mut indented = editor_with("foo\n    bar\n");
+        apply(&mut indented, &[Command::JoinLines]);
+        assert_eq!(line(&indented, 0), "foo bar");
+    }
+
+    #[test]
+    fn join_from_an_empty_line_adds_no_space() {
+        let mut state = editor_with("\nbar\n");
+        apply(&mut state, &[Command::JoinLines]);
+        assert_eq!(line(&state, 0), "bar");
+    }
+
+    #[test]
+    fn join_on_the_last_line_is_a_no_op() {
+        let mut state = editor_with("foo\n");
+        apply(&mut state, &[Command::JoinLines]);
+        assert_eq!(line(&state, 0), "foo");
+    }
+
+    #[test]
+    fn open_below_preserves_indent_and_enters_insert_mode() {
+        let mut state = editor_with("    foo\n");
+        apply(&mut state, &[Command::OpenLineBelow]);
+
+        assert_eq!(line(&state, 0), "    foo");
+        assert_eq!(line(&state, 1), "    ");
+        let position = state.window.cursor.position;
+        assert_eq!((position.x, position.y), (4, 1));
+        assert!(matches!(state.mode, Mode::Insert));
+    }
+
+    #[test]
+    fn open_above_preserves_indent_and_enters_insert_mode() {
+        let mut state = editor_with("    foo\n");
+        apply(&mut state, &[Command::OpenLineAbove]);
+
+        assert_eq!(line(&state, 0), "    ");
+        assert_eq!(line(&state, 1), "    foo");
+        let position = state.window.cursor.position;
+        assert_eq!((position.x, position.y), (4, 0));
+        assert!(matches!(state.mode, Mode::Insert));
+    }
+
+    #[test]
+    fn indent_adds_a_level_to_every_selected_line() {
+        let mut state = editor_with("one\ntwo\nthree\n");
+        apply(
+            &mut state,
+            &[
+                Command::SwitchMode(Mode::Visual),
+                Command::MoveCursorDown,
+                Command::MoveCursorDown,
+                Command::IndentSelection,
+            ],
+        );
+
+        assert_eq!(line(&state, 0), "    one");
+        assert_eq!(line(&state, 1), "    two");
+        assert_eq!(line(&state, 2), "    three");
+        // Back in normal mode, on the first non-blank of the first line.
+        assert!(matches!(state.mode, Mode::Normal));
+        assert_eq!(state.window.cursor.position.x, 4);
+    }
+
+    #[test]
+    fn outdent_removes_one_level_of_tabs_or_spaces() {
+        let mut state = editor_with("\tone\n    two\n  three\n");
+        apply(
+            &mut state,
+            &[
+                Command::SwitchMode(Mode::Visual),
+                Command::MoveCursorDown,
+                Command::MoveCursorDown,
+                Command::OutdentSelection,
+            ],
+        );
+
+        // One tab is a full level; fewer spaces than a level all go.
+        assert_eq!(line(&state, 0), "one");
+        assert_eq!(line(&state, 1), "two");
+        assert_eq!(line(&state, 2), "three");
+    }
+
+    #[test]
+    fn indent_leaves_empty_lines_alone() {
+        let mut state = editor_with("one\n\nthree\n");
+        apply(
+            &mut state,
+            &[
+                Command::SwitchMode(Mode::Visual),
+                Command::MoveCursorDown,
+                Command::MoveCursorDown,
+                Command::IndentSelection,
+            ],
+        );
+
+        assert_eq!(line(&state, 0), "    one");
+        assert_eq!(line(&state, 1), "");
+        assert_eq!(line(&state, 2), "    three");
+    }
+
+    #[test]
+    fn a_new_line_inherits_the_current_indent() {
+        let mut state = editor_with("    foobar\n");
+        state.window.cursor.position = Position { x: 7, y: 0 };
+
+        apply(&mut state, &[Command::InsertChar('\n')]);
+
+        assert_eq!(line(&state, 0), "    foo");
+        assert_eq!(line(&state, 1), "    bar");
+        let position = state.window.cursor.position;
+        assert_eq!((position.x, position.y), (4, 1));
+    }
+
+    #[test]
+    fn a_new_line_after_an_opening_brace_indents_one_more_level() {
+        let mut state = editor_with("fn main() {\n");
+        state.window.cursor.position = Position { x: 11, y: 0 };
+
+        apply(&mut state, &[Command::InsertChar('\n')]);
+
+        assert_eq!(line(&state, 1), INDENT_UNIT);
+        assert_eq!(state.window.cursor.position.x, INDENT_UNIT.len());
+    }
+
+    #[test]
+    fn typing_an_opener_inserts_the_pair() {
+        let mut state = editor_with("");
+
+        apply(&mut state, &[Command::InsertChar('(')]);
+
+        // Both halves land, with the cursor between them.
+        assert_eq!(line(&state, 0), "()");
+        assert_eq!(state.window.cursor.position.x, 1);
+    }
+
+    #[test]
+    fn typing_the_closer_skips_over_it() {
+        let mut state = editor_with("");
+
+        apply(
+            &mut state,
+            &[Command::InsertChar('('), Command::InsertChar(')')],
+        );
+
+        // The `)` under the cursor is stepped over, not doubled.
+        assert_eq!(line(&state, 0), "()");
+        assert_eq!(state.window.cursor.position.x, 2);
+    }
+
+    #[test]
+    fn surround_wraps_the_selection_in_the_pair() {
+        let mut state = editor_with("hello\n");
+        apply(
+            &mut state,
+            &[
+                Command::SwitchMode(Mode::Visual),
+                Command::MoveCursorEndOfLine,
+                Command::SurroundSelection('('),
+            ],
+        );
+
+        assert_eq!(line(&state, 0), "(hello)");
+        // Back in normal mode, on the opening character.
+        assert!(matches!(state.mode, Mode::Normal));
+        assert_eq!(state.window.cursor.position.x, 0);
+    }
+
+    #[test]
+    fn uniq_drops_duplicates_keeping_first_occurrences() {
+        // Both adjacent and non-adjacent repeats go.
+        let mut state = editor_with("a\nb\na\nb\nb\n");
+        state.unique_lines();
+
+        assert_eq!(state.window.buffer.len_nonempty_lines(), 2);
+        assert_eq!(line(&state, 0), "a");
+        assert_eq!(line(&state, 1), "b");
+    }
+
+    #[test]
+    fn uniq_leaves_distinct_lines_alone() {
+        let mut state = editor_with("a\nb\nc\n");
+        state.unique_lines();
+
+        assert_eq!(state.window.buffer.len_nonempty_lines(), 3);
+        assert_eq!(line(&state, 0), "a");
+        assert_eq!(line(&state, 1), "b");
+        assert_eq!(line(&state, 2), "c");
+    }
+
+    #[test]
+    fn jumps_walk_back_and_forward_through_the_list() {
+        let mut state = editor_with(&"x\n".repeat(100));
+
+        // Two jumps leave (0, 0) and line 49 on the list.
+        apply(&mut state, &[Command::GotoLine(50), Command::GotoLine(80)]);
+        assert_eq!(state.window.cursor.position.y, 79);
+
+        apply(&mut state, &[Command::JumpBack]);
+        assert_eq!(state.window.cursor.position.y, 49);
+        apply(&mut state, &[Command::JumpBack]);
+        assert_eq!(state.window.cursor.position.y, 0);
+
+        // Walking past the oldest entry stays put.
+        apply(&mut state, &[Command::JumpBack]);
+        assert_eq!(state.window.cursor.position.y, 0);
+
+        apply(&mut state, &[Command::JumpForward]);
+        assert_eq!(state.window.cursor.position.y, 49);
+        apply(&mut state, &[Command::JumpForward]);
+        assert_eq!(state.window.cursor.position.y, 79);
+    }
+
+    #[test]
+    fn jumping_back_from_the_newest_entry_is_not_a_no_op() {
+        let mut state = editor_with(&"x\n".repeat(100));
+
+        // The second `:50` records line 49 — exactly where the cursor
+        // already stands, so the first `Ctrl-o` must skip past it.
+        apply(&mut state, &[Command::GotoLine(50), Command::GotoLine(50)]);
+        assert_eq!(state.window.cursor.position.y, 49);
+
+        apply(&mut state, &[Command::JumpBack]);
+        assert_eq!(state.window.cursor.position.y, 0);
+
+        // And `Ctrl-i` still returns to where the walk started.
+        apply(&mut state, &[Command::JumpForward]);
+        assert_eq!(state.window.cursor.position.y, 49);
+    }
+
+    #[test]
+    fn count_digits_accumulate_left_to_right() {
+        // `5l` runs the motion five times.
+        assert_eq!(push_count_digit(None, 5), Some(5));
+        assert_eq!(resolve_count(Some(5), 1), 5);
+
+        // `10G`: the digits build 10, then `G` takes it; a plain `G`
+        // falls back to the default (the last line).
+        let pending = push_count_digit(None, 1);
+        let pending = push_count_digit(pending, 0);
+        assert_eq!(pending, Some(10));
+        assert_eq!(resolve_count(pending, 99), 10);
+        assert_eq!(resolve_count(None, 99), 99);
+    }
+
+    #[test]
+    fn a_leading_zero_is_not_a_count() {
+        // Bare `0` stays the start-of-line motion...
+        assert_eq!(push_count_digit(None, 0), None);
+        // ...but after another digit it extends the count.
+        assert_eq!(push_count_digit(Some(1), 0), Some(10));
+    }
+
+    #[test]
+    fn a_huge_count_saturates_instead_of_overflowing() {
+        let pending = Some(usize::MAX / 10 + 1);
+        assert_eq!(push_count_digit(pending, 9), Some(usize::MAX));
+    }
+
+    #[test]
+    fn aliases_expand_through_chains() {
+        let aliases = HashMap::from([
+            ("x".to_string(), "wq".to_string()),
+            ("write".to_string(), "w".to_string()),
+            ("save".to_string(), "write".to_string()),
+        ]);
+
+        assert_eq!(expand_alias(&aliases, "x".to_string()), Ok("wq".to_string()));
+        // Chains resolve until a non-alias is reached.
+        assert_eq!(
+            expand_alias(&aliases, "save".to_string()),
+            Ok("w".to_string())
+        );
+        assert_eq!(
+            expand_alias(&aliases, "sort".to_string()),
+            Ok("sort".to_string())
+        );
+    }
+
+    #[test]
+    fn aliases_carry_their_arguments_over() {
+        let aliases = HashMap::from([("write".to_string(), "w".to_string())]);
+
+        assert_eq!(
+            expand_alias(&aliases, "write notes.txt".to_string()),
+            Ok("w notes.txt".to_string())
+        );
+    }
+
+    #[test]
+    fn an_alias_cycle_is_rejected() {
+        let aliases = HashMap::from([
+            ("a".to_string(), "b".to_string()),
+            ("b".to_string(), "a".to_string()),
+        ]);
+
+        assert_eq!(expand_alias(&aliases, "a".to_string()), Err("a".to_string()));
+    }
+
+    #[test]
+    fn command_line_parses_the_simple_commands() {
+        assert_eq!(parse_command_line("q"), Some(CommandLineAction::Quit));
+        assert_eq!(parse_command_line("w"), Some(CommandLineAction::Save));
+        assert_eq!(
+            parse_command_line("wq"),
+            Some(CommandLineAction::SaveAndQuit)
+        );
+        assert_eq!(parse_command_line("uniq"), Some(CommandLineAction::Unique));
+        assert_eq!(
+            parse_command_line("nohl"),
+            Some(CommandLineAction::ClearHighlights)
+        );
+    }
+
+    #[test]
+    fn command_line_parses_the_sort_variants() {
+        assert_eq!(
+            parse_command_line("sort"),
+            Some(CommandLineAction::Sort {
+                reverse: false,
+                case_insensitive: false,
+            })
+        );
+        assert_eq!(
+            parse_command_line("sort!"),
+            Some(CommandLineAction::Sort {
+                reverse: true,
+                case_insensitive: false,
+            })
+        );
+        assert_eq!(
+            parse_command_line("sort i"),
+            Some(CommandLineAction::Sort {
+                reverse: false,
+                case_insensitive: true,
+            })
+        );
+        assert_eq!(
+            parse_command_line("sort! i"),
+            Some(CommandLineAction::Sort {
+                reverse: true,
+                case_insensitive: true,
+            })
+        );
+    }
+
+    #[test]
+    fn command_line_parses_the_commands_with_arguments() {
+        assert_eq!(
+            parse_command_line("w notes.txt"),
+            Some(CommandLineAction::SaveAs("notes.txt".to_string()))
+        );
+        assert_eq!(
+            parse_command_line("reflow 72"),
+            Some(CommandLineAction::Reflow(72))
+        );
+        assert_eq!(
+            parse_command_line("42"),
+            Some(CommandLineAction::GotoLine(42))
+        );
+    }
+
+    #[test]
+    fn command_line_rejects_unknown_commands() {
+        assert_eq!(parse_command_line("frobnicate"), None);
+        assert_eq!(parse_command_line("reflow wide"), None);
+        assert_eq!(parse_command_line("q!"), None);
+        assert_eq!(parse_command_line(""), None);
+    }
+}
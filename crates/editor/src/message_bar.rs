@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use renderer::{
+    terminal::TerminalInterface, Color, Component, Renderer, RendererError, TerminalCommand,
+};
+use utils::Size;
+
+/// How many messages are kept around; older ones are dropped first.
+const MAX_MESSAGES: usize = 16;
+
+/// How long a message stays on screen before it expires.
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How loud a message is, which picks its color on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn color(self) -> Color {
+        match self {
+            Severity::Info => Color::Reset,
+            Severity::Warning => Color::Indexed(3), // Yellow.
+            Severity::Error => Color::Indexed(1),   // Red.
+        }
+    }
+}
+
+struct Message {
+    text: String,
+    severity: Severity,
+    posted_at: Instant,
+}
+
+/// A one-line notification bar drawn over the status bar while a message
+/// is live. Messages queue up in a small ring buffer and expire on their
+/// own after a timeout, newest shown first.
+pub struct MessageBar {
+    messages: VecDeque<Message>,
+    pub size: Size,
+}
+
+impl MessageBar {
+    pub fn new(size: Size) -> Self {
+        Self {
+            messages: VecDeque::new(),
+            size,
+        }
+    }
+
+    /// Queues a message for display. The oldest message is dropped once
+    /// the ring buffer is full.
+    pub fn push(&mut self, text: impl Into<String>, severity: Severity) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+
+        self.messages.push_back(Message {
+            text: text.into(),
+            severity,
+            posted_at: Instant::now(),
+        });
+    }
+
+    /// Drops messages older than the timeout. Returns whether anything
+    /// expired, so the caller knows the screen needs a redraw.
+    pub fn expire(&mut self) -> bool {
+        let before = self.messages.len();
+        self.messages
+            .retain(|message| message.posted_at.elapsed() < MESSAGE_TIMEOUT);
+
+        self.messages.len() != before
+    }
+
+    /// The message currently shown, if any: the newest one still alive.
+    fn current(&self) -> Option<&Message> {
+        self.messages.back()
+    }
+}
+
+impl Component for MessageBar {
+    fn render<T>(&mut self, renderer: &mut Renderer<T>) -> Result<(), RendererError>
+    where
+        T: TerminalInterface,
+    {
+        let Some(message) = self.current() else {
+            return Ok(());
+        };
+
+        // Truncate and pad by chars, not bytes: messages carry user input
+        // (file names, search queries) that may be non-ASCII.
+        let mut text: String = message.text.chars().take(self.size.width).collect();
+        let text_len = text.chars().count();
+        if text_len < self.size.width {
+            text.push_str(&" ".repeat(self.size.width - text_len));
+        }
+
+        renderer.enqueue_command(TerminalCommand::MoveCursor(0, self.size.height - 1));
+        renderer.enqueue_command(TerminalCommand::PrintStyled(vec![(
+            text,
+            message.severity.color(),
+        )]));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar() -> MessageBar {
+        MessageBar::new(Size {
+            width: 80,
+            height: 24,
+        })
+    }
+
+    #[test]
+    fn the_newest_message_is_shown() {
+        let mut bar = bar();
+        bar.push("first", Severity::Info);
+        bar.push("second", Severity::Warning);
+
+        let current = bar.current().expect("a live message");
+        assert_eq!(current.text, "second");
+        assert_eq!(current.severity, Severity::Warning);
+    }
+
+    #[test]
+    fn a_fresh_message_does_not_expire() {
+        let mut bar = bar();
+        bar.push("hello", Severity::Info);
+
+        // Nothing is five seconds old yet, so nothing expires and no
+        // redraw is requested.
+        assert!(!bar.expire());
+        assert!(bar.current().is_some());
+    }
+
+    #[test]
+    fn the_ring_buffer_drops_the_oldest_message() {
+        let mut bar = bar();
+        for i in 0..MAX_MESSAGES + 1 {
+            bar.push(format!("message {i}"), Severity::Info);
+        }
+
+        assert_eq!(bar.messages.len(), MAX_MESSAGES);
+        assert_eq!(bar.messages.front().expect("a message").text, "message 1");
+    }
+
+    #[test]
+    fn severities_map_to_their_colors() {
+        assert!(matches!(Severity::Info.color(), Color::Reset));
+        assert!(matches!(Severity::Warning.color(), Color::Indexed(3)));
+        assert!(matches!(Severity::Error.color(), Color::Indexed(1)));
+    }
+}
@@ -4,17 +4,18 @@ use utils::Cursor;
 
 use crate::buffer::Buffer;
 
-/// Moves the cursor to the left, the Buffer does not quite matter here.
-pub fn move_cursor_left(cursor: &mut Cursor) {
+/// Moves the cursor one grapheme cluster to the left.
+pub fn move_cursor_left(cursor: &mut Cursor, buffer: &Buffer) {
     if cursor.position.x > 0 {
-        cursor.position.x -= 1;
+        cursor.position.x = buffer.prev_grapheme_x(cursor.position);
 
         // Updates the desired x.
         cursor.desired_x = cursor.position.x;
     }
 }
 
-/// Moves the cursor to the right, respecting the boundaries of a Buffer.
+/// Moves the cursor one grapheme cluster to the right, respecting the
+/// boundaries of a Buffer.
 ///
 /// `exceed_line` means if we want the cursor to be able to move beyond the visible part
 /// of the line, which means we are counting the '\n' character.
@@ -26,7 +27,7 @@ pub fn move_cursor_right(cursor: &mut Cursor, buffer: &Buffer, exceed_line: bool
     };
 
     if cursor.position.x < line_length {
-        cursor.position.x += 1;
+        cursor.position.x = buffer.next_grapheme_x(cursor.position).min(line_length);
 
         // Updates the desired x.
         cursor.desired_x = cursor.position.x;
@@ -40,8 +41,10 @@ pub fn move_cursor_up(cursor: &mut Cursor, buffer: &Buffer) {
         let line_length = buffer.get_visible_line_length(cursor.position.y);
 
         // Updates the horizontal position to be either the desired x
-        // or the line length.
+        // or the line length, snapped so the column from the previous
+        // line never lands inside a grapheme cluster.
         cursor.position.x = min(cursor.desired_x, line_length);
+        cursor.position.x = buffer.snap_to_grapheme_x(cursor.position);
     }
 }
 
@@ -52,8 +55,10 @@ pub fn move_cursor_down(cursor: &mut Cursor, buffer: &Buffer) {
         let line_length = buffer.get_visible_line_length(cursor.position.y);
 
         // Updates the horizontal position to be either the desired x
-        // or the line length.
+        // or the line length, snapped so the column from the previous
+        // line never lands inside a grapheme cluster.
         cursor.position.x = min(cursor.desired_x, line_length);
+        cursor.position.x = buffer.snap_to_grapheme_x(cursor.position);
     }
 }
 
@@ -0,0 +1,111 @@
+use renderer::{
+    terminal::TerminalInterface, Component, Renderer, RendererError, TerminalCommand,
+};
+use utils::Size;
+
+/// A vertical scrollbar drawn as a column of block characters on the
+/// right edge of the viewport.
+pub struct Scrollbar {
+    scroll_offset: usize, // First visible line of the window.
+    total_lines: usize,
+    pub size: Size, // Size of the viewport the scrollbar annotates.
+}
+
+impl Scrollbar {
+    pub fn new(size: Size) -> Self {
+        Self {
+            scroll_offset: 0,
+            total_lines: 0,
+            size,
+        }
+    }
+
+    pub fn update(&mut self, scroll_offset: usize, total_lines: usize) {
+        self.scroll_offset = scroll_offset;
+        self.total_lines = total_lines;
+    }
+
+    /// Returns the thumb as `(start_row, length)` within the track, or
+    /// `None` when the whole buffer fits on screen.
+    fn thumb(&self) -> Option<(usize, usize)> {
+        // The last row belongs to the status bar.
+        let track = self.size.height.saturating_sub(1);
+
+        if track == 0 || self.total_lines <= track {
+            return None;
+        }
+
+        // Thumb length is proportional to how much of the buffer is visible.
+        let length = ((track * track) / self.total_lines).max(1);
+
+        // And its position to how far down we are scrolled.
+        let max_offset = self.total_lines - track;
+        let start = (self.scroll_offset.min(max_offset) * (track - length)) / max_offset;
+
+        Some((start, length))
+    }
+}
+
+impl Component for Scrollbar {
+    fn render<T>(&mut self, renderer: &mut Renderer<T>) -> Result<(), RendererError>
+    where
+        T: TerminalInterface,
+    {
+        let Some((start, length)) = self.thumb() else {
+            return Ok(());
+        };
+
+        let track = self.size.height.saturating_sub(1);
+        let column = self.size.width.saturating_sub(1);
+
+        for row in 0..track {
+            renderer.enqueue_command(TerminalCommand::MoveCursor(column, row));
+
+            let glyph = if row >= start && row < start + length {
+                "█"
+            } else {
+                "░"
+            };
+            renderer.enqueue_command(TerminalCommand::Print(glyph.to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrollbar(height: usize, scroll_offset: usize, total_lines: usize) -> Scrollbar {
+        let mut scrollbar = Scrollbar::new(Size { width: 80, height });
+        scrollbar.update(scroll_offset, total_lines);
+        scrollbar
+    }
+
+    #[test]
+    fn no_thumb_when_the_buffer_fits() {
+        // Height 11 leaves a 10-row track after the status bar.
+        assert!(scrollbar(11, 0, 10).thumb().is_none());
+        assert!(scrollbar(11, 0, 0).thumb().is_none());
+        assert!(scrollbar(1, 0, 100).thumb().is_none());
+    }
+
+    #[test]
+    fn thumb_length_is_proportional_to_visibility() {
+        // Half the buffer visible: the thumb covers half the track.
+        assert_eq!(scrollbar(11, 0, 20).thumb(), Some((0, 5)));
+        // A huge buffer still gets at least a one-row thumb.
+        assert_eq!(scrollbar(11, 0, 10_000).thumb(), Some((0, 1)));
+    }
+
+    #[test]
+    fn thumb_tracks_the_scroll_position() {
+        // Scrolled to the end, the thumb sits at the bottom of the track.
+        assert_eq!(scrollbar(11, 10, 20).thumb(), Some((5, 5)));
+        // Halfway down lands it mid-track.
+        assert_eq!(scrollbar(11, 5, 20).thumb(), Some((2, 5)));
+        // An offset past the end clamps instead of overflowing.
+        assert_eq!(scrollbar(11, 99, 20).thumb(), Some((5, 5)));
+    }
+}
@@ -9,6 +9,7 @@ pub struct StatusBar {
     file_name: Option<String>,
     cursor_position: Position,
     pub size: Size,
+    prompt: Option<String>, // When set, drawn in place of the regular status line.
 }
 
 impl StatusBar {
@@ -18,13 +19,21 @@ impl StatusBar {
             file_name: None,
             cursor_position: Position::new(),
             size,
+            prompt: None,
         }
     }
 
-    pub fn update(&mut self, mode: Mode, file_name: Option<String>, cursor_position: Position) {
+    pub fn update(
+        &mut self,
+        mode: Mode,
+        file_name: Option<String>,
+        cursor_position: Position,
+        prompt: Option<String>,
+    ) {
         self.current_mode = mode;
         self.file_name = file_name;
         self.cursor_position = cursor_position;
+        self.prompt = prompt;
     }
 }
 
@@ -37,6 +46,9 @@ impl Component for StatusBar {
         let mode_str = match self.current_mode {
             Mode::Normal => "NORMAL",
             Mode::Insert => "INSERT",
+            Mode::Visual => "VISUAL",
+            Mode::Search => "SEARCH",
+            Mode::Command => "COMMAND",
         };
 
         let file_name = self.file_name.as_deref().unwrap_or("[No Name]");
@@ -46,8 +58,12 @@ impl Component for StatusBar {
             self.cursor_position.x + 1
         );
 
-        // Format `StatusBar`.
-        let status = format!(" {} | {} | {}", mode_str, file_name, cursor_pos);
+        // Format `StatusBar`. A prompt (like the search input) takes over
+        // the whole line.
+        let status = match &self.prompt {
+            Some(prompt) => prompt.clone(),
+            None => format!(" {} | {} | {}", mode_str, file_name, cursor_pos),
+        };
 
         // Make sure it fits the screen.
         let mut status_bar = status;
@@ -1,13 +1,17 @@
 // TODO: Implement specific redrawing based on changes, not redrawing the entire buffer all the time.
 use renderer::{
-    terminal::{Terminal, TerminalInterface},
-    Component, Renderer, RendererError, TerminalCommand,
+    terminal::TerminalInterface,
+    Color, Component, Renderer, RendererError, TerminalCommand,
 };
 use text_engine::{Rope, RopeSlice};
 use utils::{build_welcome_message, Cursor, Position, Size};
 
 use crate::{buffer::Buffer, EditorError};
 
+/// The highlight used for search matches (yellow in the 256-color
+/// palette), distinct from the reverse-video selection.
+const SEARCH_MATCH_COLOR: Color = Color::Indexed(3);
+
 /// Represents a window in the terminal.
 pub struct Window {
     pub buffer: Buffer,
@@ -15,6 +19,8 @@ pub struct Window {
     scroll_offset: Position,
     pub viewport_size: Size,
     pub needs_redraw: bool,
+    pub selection_anchor: Option<Position>, // Set while in visual mode.
+    pub search_matches: Vec<(Position, usize)>, // Highlighted `(start, char length)` matches.
 }
 
 impl Window
@@ -22,12 +28,8 @@ impl Window
     /// Loads a `Window` from a `Buffer` (can be `None`).
     pub fn from_file(
         file_path: Option<String>,
+        viewport_size: Size,
     ) -> Result<Self, EditorError> {
-        let (width, height) = Terminal::size()
-            .map_err(|e| EditorError::RenderError(format!("Could not initialize viewport: {e}")))?;
-
-        let viewport_size = Size { width, height };
-
         let buffer = if let Some(path) = file_path {
             Buffer::open(path)?
         } else {
@@ -40,6 +42,8 @@ impl Window
             scroll_offset: Position::new(),
             viewport_size,
             needs_redraw: true, // Initial drawing
+            selection_anchor: None,
+            search_matches: Vec::new(),
         })
     }
 
@@ -60,14 +64,105 @@ impl Window
         }
     }
 
-    /// Renders a single row in the `Window`.
-    fn render_row<T: TerminalInterface> (&self, row: usize, slice: RopeSlice, renderer: &mut Renderer<T>) {
+    /// Renders a single row in the `Window` as styled runs: search
+    /// matches get a distinct color, everything else keeps the default.
+    /// Tree-sitter can later split lines into one run per token.
+    fn render_row<T: TerminalInterface>(
+        &self,
+        row: usize,
+        line_idx: usize,
+        slice: RopeSlice,
+        renderer: &mut Renderer<T>,
+    ) {
         renderer.enqueue_command(TerminalCommand::MoveCursor(0, row));
 
-        // Since this runs in O(log N), it's better then to turn it
-        // into a string or something.
-        let rope = Rope::from(slice);
-        renderer.enqueue_command(TerminalCommand::PrintRope(rope));
+        let text = slice.to_string();
+        let ranges = self.match_columns_for_line(line_idx, slice.len_chars());
+        if ranges.is_empty() {
+            renderer.enqueue_command(TerminalCommand::PrintStyled(vec![(text, Color::Reset)]));
+            return;
+        }
+
+        // Split the visible text into alternating default and highlight
+        // runs.
+        let chars: Vec<char> = text.chars().collect();
+        let mut runs = Vec::new();
+        let mut at = 0;
+        for (start, end) in ranges {
+            if at < start {
+                runs.push((chars[at..start].iter().collect(), Color::Reset));
+            }
+            runs.push((chars[start..end].iter().collect(), SEARCH_MATCH_COLOR));
+            at = end;
+        }
+        if at < chars.len() {
+            runs.push((chars[at..].iter().collect(), Color::Reset));
+        }
+
+        renderer.enqueue_command(TerminalCommand::PrintStyled(runs));
+    }
+
+    /// Returns the column ranges (end exclusive) of the search matches
+    /// on a line, translated into the visible slice and ordered left to
+    /// right.
+    fn match_columns_for_line(&self, line_idx: usize, visible_len: usize) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = self
+            .search_matches
+            .iter()
+            .filter(|(position, _)| position.y == line_idx)
+            .map(|(position, len)| {
+                let start = position
+                    .x
+                    .saturating_sub(self.scroll_offset.x)
+                    .min(visible_len);
+                let end = (position.x + len)
+                    .saturating_sub(self.scroll_offset.x)
+                    .min(visible_len);
+                (start, end)
+            })
+            .filter(|(start, end)| start < end)
+            .collect();
+
+        ranges.sort_unstable();
+        ranges
+    }
+
+    /// Renders a single row with part of it highlighted (visual mode selection).
+    /// `selection_start` and `selection_end` are absolute columns (end exclusive).
+    fn render_row_with_selection<T: TerminalInterface>(
+        &self,
+        row: usize,
+        slice: RopeSlice,
+        selection_start: usize,
+        selection_end: usize,
+        renderer: &mut Renderer<T>,
+    ) {
+        renderer.enqueue_command(TerminalCommand::MoveCursor(0, row));
+
+        // Translate the absolute columns into columns of the visible slice.
+        let visible_len = slice.len_chars();
+        let start = selection_start
+            .saturating_sub(self.scroll_offset.x)
+            .min(visible_len);
+        let end = selection_end
+            .saturating_sub(self.scroll_offset.x)
+            .min(visible_len);
+
+        if start > 0 {
+            renderer.enqueue_command(TerminalCommand::PrintRope(Rope::from(slice.slice(..start))));
+        }
+
+        if start < end {
+            renderer.enqueue_command(TerminalCommand::EnableReverseVideo);
+            renderer.enqueue_command(TerminalCommand::PrintRope(Rope::from(
+                slice.slice(start..end),
+            )));
+            renderer.enqueue_command(TerminalCommand::DisableReverseVideo);
+        }
+
+        if end < visible_len {
+            renderer.enqueue_command(TerminalCommand::PrintRope(Rope::from(slice.slice(end..))));
+        }
     }
 
     /// Renders a single line with a '~' character
@@ -81,6 +176,11 @@ impl Window
     // Helpers
     //
 
+    /// Returns how far the window is scrolled.
+    pub fn scroll_offset(&self) -> Position {
+        self.scroll_offset
+    }
+
     /// Calculates all the visible lines given a start and width of the
     /// viewport.
     fn calculate_visible_text<'a>(
@@ -100,6 +200,116 @@ impl Window
         }
     }
 
+    /// Returns the selected range as `(start, end)` positions (both inclusive),
+    /// ordered so that `start` always comes before `end` in the buffer.
+    pub fn selection_range(&self) -> Option<(Position, Position)> {
+        let anchor = self.selection_anchor?;
+        let cursor = self.cursor.position;
+
+        if (anchor.y, anchor.x) <= (cursor.y, cursor.x) {
+            Some((anchor, cursor))
+        } else {
+            Some((cursor, anchor))
+        }
+    }
+
+    /// Returns the selected columns of a given line as `(start_x, end_x)`
+    /// (end exclusive), or `None` if the line is not part of the selection.
+    fn selection_columns_for_line(&self, line_idx: usize, line_len: usize) -> Option<(usize, usize)> {
+        let (start, end) = self.selection_range()?;
+
+        if line_idx < start.y || line_idx > end.y {
+            return None;
+        }
+
+        let start_x = if line_idx == start.y { start.x } else { 0 };
+        // The selection end is inclusive, hence the + 1.
+        let end_x = if line_idx == end.y { end.x + 1 } else { line_len };
+
+        Some((start_x, end_x.min(line_len)))
+    }
+
+    /// Scrolls the view by `delta` lines (negative is up), dragging the
+    /// cursor along so it stays inside the visible range.
+    pub fn scroll_vertically(&mut self, delta: isize) {
+        let max_offset = self.buffer.len_nonempty_lines().saturating_sub(1);
+        let offset = self.scroll_offset.y as isize + delta;
+        self.scroll_offset.y = offset.clamp(0, max_offset as isize) as usize;
+
+        let height = self.viewport_size.height.saturating_sub(1); // Status bar.
+        let top = self.scroll_offset.y;
+        let bottom = (top + height.saturating_sub(1)).min(max_offset);
+
+        self.cursor.position.y = self.cursor.position.y.clamp(top, bottom);
+        self.cursor.position.x = self
+            .cursor
+            .desired_x
+            .min(self.buffer.get_visible_line_length(self.cursor.position.y));
+    }
+
+    /// Moves the cursor and the view half a screen down or up, like vim's
+    /// `Ctrl-d` and `Ctrl-u`.
+    pub fn half_page_scroll(&mut self, down: bool) {
+        let height = self.viewport_size.height.saturating_sub(1); // Status bar.
+        self.scroll_cursor_by((height / 2).max(1) as isize, down);
+    }
+
+    /// Moves the cursor and the view a full screen down or up, like vim's
+    /// `Ctrl-f` and `Ctrl-b`.
+    pub fn page_scroll(&mut self, down: bool) {
+        let height = self.viewport_size.height.saturating_sub(1); // Status bar.
+        self.scroll_cursor_by(height.max(1) as isize, down);
+    }
+
+    /// Moves the cursor and the view `amount` lines in one direction,
+    /// clamping both at the buffer ends.
+    fn scroll_cursor_by(&mut self, amount: isize, down: bool) {
+        let delta = if down { amount } else { -amount };
+
+        let max_line = self.buffer.len_nonempty_lines().saturating_sub(1) as isize;
+
+        let y = (self.cursor.position.y as isize + delta).clamp(0, max_line) as usize;
+        self.cursor.position.y = y;
+        self.cursor.position.x = self
+            .cursor
+            .desired_x
+            .min(self.buffer.get_visible_line_length(y));
+
+        // Shift the view by the same amount so the cursor keeps its row on
+        // screen; scroll_to_cursor fixes any overshoot at the buffer edges.
+        self.scroll_offset.y =
+            (self.scroll_offset.y as isize + delta).clamp(0, max_line) as usize;
+    }
+
+    /// Scrolls vertically so the cursor line shows at `row` of the content
+    /// area, clamped so the view never runs past the buffer.
+    fn scroll_cursor_to_row(&mut self, row: usize) {
+        let max_offset = self.buffer.len_nonempty_lines().saturating_sub(1);
+        self.scroll_offset.y = self
+            .cursor
+            .position
+            .y
+            .saturating_sub(row)
+            .min(max_offset);
+    }
+
+    /// Centers the view on the cursor line, like vim's `zz`.
+    pub fn center_view(&mut self) {
+        let height = self.viewport_size.height.saturating_sub(1); // Status bar.
+        self.scroll_cursor_to_row(height / 2);
+    }
+
+    /// Scrolls so the cursor line becomes the top of the view (`zt`).
+    pub fn view_top(&mut self) {
+        self.scroll_cursor_to_row(0);
+    }
+
+    /// Scrolls so the cursor line becomes the bottom of the view (`zb`).
+    pub fn view_bottom(&mut self) {
+        let height = self.viewport_size.height.saturating_sub(1); // Status bar.
+        self.scroll_cursor_to_row(height.saturating_sub(1));
+    }
+
     /// Adjust the cursor scrolling based on the `scroll_offset` and `viewport_size`.
     pub fn scroll_to_cursor(&mut self) {
         let width = self.viewport_size.width;
@@ -148,7 +358,16 @@ impl Component for Window {
                     let line = self.buffer.get_trimmed_line(line_idx);
                     let visible_text = self.calculate_visible_text(line, self.scroll_offset.x, width);
 
-                    self.render_row(current_row, visible_text, renderer);
+                    match self.selection_columns_for_line(line_idx, line.len_chars()) {
+                        Some((start_x, end_x)) => self.render_row_with_selection(
+                            current_row,
+                            visible_text,
+                            start_x,
+                            end_x,
+                            renderer,
+                        ),
+                        None => self.render_row(current_row, line_idx, visible_text, renderer),
+                    }
                 } else {
                     self.render_empty_row(current_row, renderer);
                 }
@@ -170,3 +389,132 @@ impl Component for Window {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(text: &str, width: usize, height: usize) -> Window {
+        let mut buffer = Buffer::new();
+        buffer.insert_text(Position { x: 0, y: 0 }, text);
+
+        Window {
+            buffer,
+            cursor: Cursor::new(),
+            scroll_offset: Position::new(),
+            viewport_size: Size { width, height },
+            needs_redraw: true,
+            selection_anchor: None,
+            search_matches: Vec::new(),
+        }
+    }
+
+    fn at(x: usize, y: usize) -> Position {
+        Position { x, y }
+    }
+
+    #[test]
+    fn center_top_and_bottom_place_the_cursor_line() {
+        // Height 11 leaves 10 content rows above the status bar.
+        let mut window = window(&"x\n".repeat(100), 80, 11);
+        window.cursor.position = at(0, 50);
+
+        window.center_view();
+        assert_eq!(window.scroll_offset.y, 45);
+
+        window.view_top();
+        assert_eq!(window.scroll_offset.y, 50);
+
+        window.view_bottom();
+        assert_eq!(window.scroll_offset.y, 41);
+    }
+
+    #[test]
+    fn view_placement_clamps_near_the_buffer_start() {
+        let mut window = window(&"x\n".repeat(100), 80, 11);
+        window.cursor.position = at(0, 2);
+
+        // There are not enough lines above to center on or push down.
+        window.center_view();
+        assert_eq!(window.scroll_offset.y, 0);
+
+        window.view_bottom();
+        assert_eq!(window.scroll_offset.y, 0);
+    }
+
+    #[test]
+    fn half_page_scroll_moves_cursor_and_view_together() {
+        // Height 11 leaves 10 content rows, so a half page is 5.
+        let mut window = window(&"x\n".repeat(100), 80, 11);
+        window.cursor.position = at(0, 20);
+        window.scroll_offset.y = 15;
+
+        window.half_page_scroll(true);
+        assert_eq!(window.cursor.position.y, 25);
+        assert_eq!(window.scroll_offset.y, 20);
+
+        window.half_page_scroll(false);
+        assert_eq!(window.cursor.position.y, 20);
+        assert_eq!(window.scroll_offset.y, 15);
+    }
+
+    #[test]
+    fn page_scroll_clamps_at_the_buffer_ends() {
+        let mut window = window(&"x\n".repeat(15), 80, 11);
+
+        // A full page is 10 lines; two of them hit the last line.
+        window.page_scroll(true);
+        assert_eq!(window.cursor.position.y, 10);
+        window.page_scroll(true);
+        assert_eq!(window.cursor.position.y, 14);
+
+        // And back up, clamping at the first line.
+        window.page_scroll(false);
+        window.page_scroll(false);
+        assert_eq!(window.cursor.position.y, 0);
+        assert_eq!(window.scroll_offset.y, 0);
+    }
+
+    #[test]
+    fn selection_range_orders_reversed_anchors() {
+        let mut window = window("abcd\nefgh\nijkl\n", 80, 24);
+
+        // Selecting backwards (anchor after the cursor) still yields an
+        // ordered range.
+        window.selection_anchor = Some(at(3, 1));
+        window.cursor.position = at(1, 0);
+
+        let (start, end) = window.selection_range().expect("a selection");
+        assert_eq!((start.x, start.y), (1, 0));
+        assert_eq!((end.x, end.y), (3, 1));
+
+        window.selection_anchor = None;
+        assert!(window.selection_range().is_none());
+    }
+
+    #[test]
+    fn selection_columns_span_whole_middle_lines() {
+        let mut window = window("abcd\nefgh\nijkl\n", 80, 24);
+        window.selection_anchor = Some(at(1, 0));
+        window.cursor.position = at(2, 2);
+
+        // First line: from the anchor to the line end.
+        assert_eq!(window.selection_columns_for_line(0, 4), Some((1, 4)));
+        // A middle line is covered entirely.
+        assert_eq!(window.selection_columns_for_line(1, 4), Some((0, 4)));
+        // Last line: up to the cursor, inclusive.
+        assert_eq!(window.selection_columns_for_line(2, 4), Some((0, 3)));
+        // Lines outside the span are not selected.
+        assert_eq!(window.selection_columns_for_line(3, 4), None);
+    }
+
+    #[test]
+    fn selection_columns_clamp_the_inclusive_end() {
+        let mut window = window("abcd\nefgh\n", 80, 24);
+        window.selection_anchor = Some(at(0, 0));
+        window.cursor.position = at(3, 0);
+
+        // The inclusive end's `+ 1` must not run past the line length.
+        assert_eq!(window.selection_columns_for_line(0, 4), Some((0, 4)));
+    }
+}
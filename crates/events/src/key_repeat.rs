@@ -0,0 +1,156 @@
+use std::time::{Duration, Instant};
+
+use crate::KeyPress;
+
+/// Synthesizes repeat presses for a held key, giving the TUI the smooth
+/// held-`j` scrolling a GUI gets from `press_and_repeat`.
+///
+/// The terminal reports no key-up events, so "held" is inferred: a key
+/// counts as held from its press until a different key is pressed. Both
+/// the initial delay and the repeat interval are configurable, and every
+/// method takes the current time as a parameter so a test can drive a
+/// fake clock.
+pub struct KeyRepeat {
+    initial_delay: Duration,
+    repeat_interval: Duration,
+    held: Option<HeldKey>,
+}
+
+struct HeldKey {
+    key_press: KeyPress,
+    pressed_at: Instant,
+    repeats: u32, // How many repeats have fired since the press.
+}
+
+impl KeyRepeat {
+    pub fn new(initial_delay: Duration, repeat_interval: Duration) -> Self {
+        Self {
+            initial_delay,
+            repeat_interval,
+            held: None,
+        }
+    }
+
+    /// Records a physical key press at `now`. A different key moves the
+    /// hold to it; further presses of the same key (the terminal's own
+    /// auto-repeat) keep the original press time, so our repeat schedule
+    /// is not pushed back.
+    pub fn key_pressed(&mut self, key_press: KeyPress, now: Instant) {
+        match &self.held {
+            Some(held) if held.key_press == key_press => {}
+            _ => {
+                self.held = Some(HeldKey {
+                    key_press,
+                    pressed_at: now,
+                    repeats: 0,
+                })
+            }
+        }
+    }
+
+    /// Forgets the held key, e.g. when the editor loses focus.
+    pub fn clear(&mut self) {
+        self.held = None;
+    }
+
+    /// Returns the held key if a repeat is due at `now`: nothing before
+    /// the initial delay has passed, then one press per repeat interval.
+    /// At most one repeat is returned per call, so a stalled caller gets
+    /// no catch-up burst.
+    pub fn poll_repeat(&mut self, now: Instant) -> Option<KeyPress> {
+        let held = self.held.as_mut()?;
+
+        let since_press = now.saturating_duration_since(held.pressed_at);
+        if since_press < self.initial_delay {
+            return None;
+        }
+
+        let interval = self.repeat_interval.as_micros().max(1);
+        let due = 1 + ((since_press - self.initial_delay).as_micros() / interval) as u32;
+
+        if held.repeats < due {
+            held.repeats = due;
+            Some(held.key_press)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Key, Modifiers};
+
+    const DELAY: Duration = Duration::from_millis(500);
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    fn press(c: char) -> KeyPress {
+        KeyPress {
+            key: Key::Char(c),
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    #[test]
+    fn no_repeat_before_the_initial_delay() {
+        let mut repeat = KeyRepeat::new(DELAY, INTERVAL);
+        let t0 = Instant::now();
+
+        repeat.key_pressed(press('j'), t0);
+        assert!(repeat.poll_repeat(t0).is_none());
+        assert!(repeat.poll_repeat(t0 + DELAY - Duration::from_millis(1)).is_none());
+    }
+
+    #[test]
+    fn repeats_fire_once_per_interval() {
+        let mut repeat = KeyRepeat::new(DELAY, INTERVAL);
+        let t0 = Instant::now();
+        repeat.key_pressed(press('j'), t0);
+
+        // The first repeat lands at the delay, then one per interval; a
+        // second poll at the same instant must not fire again.
+        assert!(repeat.poll_repeat(t0 + DELAY).is_some());
+        assert!(repeat.poll_repeat(t0 + DELAY).is_none());
+        assert!(repeat.poll_repeat(t0 + DELAY + INTERVAL).is_some());
+    }
+
+    #[test]
+    fn a_stalled_poll_gets_no_catch_up_burst() {
+        let mut repeat = KeyRepeat::new(DELAY, INTERVAL);
+        let t0 = Instant::now();
+        repeat.key_pressed(press('j'), t0);
+
+        // Ten intervals late, only one repeat comes out of a single poll.
+        let late = t0 + DELAY + INTERVAL * 10;
+        assert!(repeat.poll_repeat(late).is_some());
+        assert!(repeat.poll_repeat(late).is_none());
+    }
+
+    #[test]
+    fn a_different_key_restarts_the_hold() {
+        let mut repeat = KeyRepeat::new(DELAY, INTERVAL);
+        let t0 = Instant::now();
+        repeat.key_pressed(press('j'), t0);
+
+        // The terminal's own auto-repeat of the same key keeps the
+        // original schedule; a new key starts its delay over.
+        repeat.key_pressed(press('j'), t0 + Duration::from_millis(400));
+        assert!(repeat.poll_repeat(t0 + DELAY).is_some());
+
+        repeat.key_pressed(press('k'), t0 + DELAY);
+        assert!(repeat.poll_repeat(t0 + DELAY + INTERVAL).is_none());
+        let repeated = repeat.poll_repeat(t0 + DELAY + DELAY).expect("a repeat of k");
+        assert!(matches!(repeated.key, Key::Char('k')));
+    }
+
+    #[test]
+    fn clear_forgets_the_held_key() {
+        let mut repeat = KeyRepeat::new(DELAY, INTERVAL);
+        let t0 = Instant::now();
+
+        repeat.key_pressed(press('j'), t0);
+        repeat.clear();
+        assert!(repeat.poll_repeat(t0 + DELAY).is_none());
+    }
+}
@@ -0,0 +1,554 @@
+use std::collections::HashMap;
+
+use utils::{CaseTransform, Command, Mode};
+
+use crate::{Key, KeyPress, Modifiers};
+
+/// Maps a key in a mode to the commands it triggers. `EventHandler` looks
+/// bindings up here instead of hard-coding them, so `app` can seed the map
+/// with user overrides before handing it over.
+pub struct Keymap {
+    bindings: HashMap<(Mode, Key, Modifiers), Vec<Command>>,
+}
+
+impl Keymap {
+    /// A keymap with no bindings at all.
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Binds a key in a mode to the commands it should produce, replacing
+    /// whatever the key was bound to before. Returns `&mut self` so
+    /// overrides can be chained.
+    pub fn bind(
+        &mut self,
+        mode: Mode,
+        key: Key,
+        modifiers: Modifiers,
+        commands: Vec<Command>,
+    ) -> &mut Self {
+        self.bindings
+            .insert((mode, key, Self::normalize(key, modifiers)), commands);
+        self
+    }
+
+    /// Looks up what a key press is bound to in a mode.
+    pub fn get(&self, mode: Mode, key_press: &KeyPress) -> Option<&[Command]> {
+        self.bindings
+            .get(&(
+                mode,
+                key_press.key,
+                Self::normalize(key_press.key, key_press.modifiers),
+            ))
+            .map(Vec::as_slice)
+    }
+
+    /// Layers `other` on top of this keymap: its bindings win where both
+    /// map the same key, everything else falls through to what was there.
+    /// This is how a user keymap overrides only a few of the defaults.
+    pub fn merge(&mut self, other: Keymap) -> &mut Self {
+        self.bindings.extend(other.bindings);
+        self
+    }
+
+    /// A keymap of the stock bindings with `overrides` layered on top.
+    pub fn layered(overrides: Keymap) -> Self {
+        let mut keymap = Self::default_bindings();
+        keymap.merge(overrides);
+        keymap
+    }
+
+    /// Shift is already encoded in the character itself (`W` vs `w`), so it
+    /// is stripped for character keys to keep lookups consistent.
+    fn normalize(key: Key, modifiers: Modifiers) -> Modifiers {
+        match key {
+            Key::Char(_) => Modifiers {
+                shift: false,
+                ..modifiers
+            },
+            _ => modifiers,
+        }
+    }
+
+    /// The stock vim-like bindings the editor ships with.
+    pub fn default_bindings() -> Self {
+        let mut keymap = Self::empty();
+        let none = Modifiers::NONE;
+        let ctrl = Modifiers::CTRL;
+
+        // Motions shared by normal and visual mode.
+        for mode in [Mode::Normal, Mode::Visual] {
+            keymap
+                .bind(mode, Key::Char('h'), none, vec![Command::MoveCursorLeft])
+                .bind(
+                    mode,
+                    Key::Char('l'),
+                    none,
+                    vec![Command::MoveCursorRight(false)],
+                )
+                .bind(mode, Key::Char('k'), none, vec![Command::MoveCursorUp])
+                .bind(mode, Key::Char('j'), none, vec![Command::MoveCursorDown])
+                .bind(
+                    mode,
+                    Key::Char('$'),
+                    none,
+                    vec![Command::MoveCursorEndOfLine],
+                )
+                .bind(
+                    mode,
+                    Key::Char('_'),
+                    none,
+                    vec![Command::MoveCursorFirstCharOfLine],
+                )
+                .bind(
+                    mode,
+                    Key::Char('w'),
+                    none,
+                    vec![Command::MoveCursorWordForward(false)],
+                )
+                .bind(
+                    mode,
+                    Key::Char('W'),
+                    none,
+                    vec![Command::MoveCursorWordForward(true)],
+                )
+                .bind(
+                    mode,
+                    Key::Char('b'),
+                    none,
+                    vec![Command::MoveCursorWordBackward(false)],
+                )
+                .bind(
+                    mode,
+                    Key::Char('B'),
+                    none,
+                    vec![Command::MoveCursorWordBackward(true)],
+                )
+                .bind(
+                    mode,
+                    Key::Char('e'),
+                    none,
+                    vec![Command::MoveCursorWordForwardEnd(false)],
+                )
+                .bind(
+                    mode,
+                    Key::Char('E'),
+                    none,
+                    vec![Command::MoveCursorWordForwardEnd(true)],
+                )
+                .bind(mode, Key::Char('G'), none, vec![Command::GotoLastLine])
+                .bind(
+                    mode,
+                    Key::Char('%'),
+                    none,
+                    vec![Command::GotoMatchingBracket],
+                )
+                .bind(mode, Key::Char('d'), ctrl, vec![Command::HalfPageDown])
+                .bind(mode, Key::Char('u'), ctrl, vec![Command::HalfPageUp])
+                .bind(mode, Key::Char('f'), ctrl, vec![Command::PageDown])
+                .bind(mode, Key::Char('b'), ctrl, vec![Command::PageUp])
+                .bind(mode, Key::PageDown, none, vec![Command::PageDown])
+                .bind(mode, Key::PageUp, none, vec![Command::PageUp]);
+
+            // Digits build a count prefix; `0` with no pending count is
+            // resolved to "start of line" by the editor.
+            for digit in 0..=9usize {
+                let c = char::from_digit(digit as u32, 10).unwrap();
+                keymap.bind(mode, Key::Char(c), none, vec![Command::CountDigit(digit)]);
+            }
+        }
+
+        // Normal mode.
+        keymap
+            .bind(Mode::Normal, Key::Char('q'), none, vec![Command::Quit])
+            .bind(
+                Mode::Normal,
+                Key::Char('i'),
+                none,
+                vec![Command::SwitchMode(Mode::Insert)],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('a'),
+                none,
+                vec![
+                    Command::MoveCursorRight(true),
+                    Command::SwitchMode(Mode::Insert),
+                ],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('v'),
+                none,
+                vec![Command::SwitchMode(Mode::Visual)],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('x'),
+                none,
+                vec![Command::DeleteCharForward],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('p'),
+                none,
+                vec![Command::Paste(true)],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('P'),
+                none,
+                vec![Command::Paste(false)],
+            )
+            .bind(Mode::Normal, Key::Char('u'), none, vec![Command::Undo])
+            .bind(Mode::Normal, Key::Char('r'), ctrl, vec![Command::Redo])
+            .bind(
+                Mode::Normal,
+                Key::Char('a'),
+                ctrl,
+                vec![Command::IncrementNumber(1)],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('x'),
+                ctrl,
+                vec![Command::IncrementNumber(-1)],
+            )
+            .bind(Mode::Normal, Key::Char('o'), ctrl, vec![Command::JumpBack])
+            .bind(
+                Mode::Normal,
+                Key::Char('i'),
+                ctrl,
+                vec![Command::JumpForward],
+            )
+            // Terminals report `Ctrl-i` as a plain tab.
+            .bind(Mode::Normal, Key::Tab, none, vec![Command::JumpForward])
+            // `;` and `,` are free here (no `f`/`t` motions yet), so they
+            // walk the changelist like vim's `g;`/`g,`.
+            .bind(
+                Mode::Normal,
+                Key::Char(';'),
+                none,
+                vec![Command::GotoLastChange],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char(','),
+                none,
+                vec![Command::GotoNextChange],
+            )
+            .bind(Mode::Normal, Key::Char('s'), ctrl, vec![Command::Save])
+            .bind(
+                Mode::Normal,
+                Key::Char('/'),
+                none,
+                vec![Command::StartSearch(true)],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('?'),
+                none,
+                vec![Command::StartSearch(false)],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('n'),
+                none,
+                vec![Command::SearchNext],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('N'),
+                none,
+                vec![Command::SearchPrev],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char(':'),
+                none,
+                vec![Command::StartCommandLine],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('.'),
+                none,
+                vec![Command::RepeatLastChange],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('J'),
+                none,
+                vec![Command::JoinLines],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('o'),
+                none,
+                vec![Command::OpenLineBelow],
+            )
+            .bind(
+                Mode::Normal,
+                Key::Char('O'),
+                none,
+                vec![Command::OpenLineAbove],
+            )
+            // Only the center placement gets a stock key: there are no
+            // multi-key sequences yet for the full `zz`/`zt`/`zb` family.
+            .bind(Mode::Normal, Key::Char('z'), none, vec![Command::CenterView]);
+
+        // Visual mode.
+        keymap
+            .bind(
+                Mode::Visual,
+                Key::Esc,
+                none,
+                vec![Command::SwitchMode(Mode::Normal)],
+            )
+            .bind(
+                Mode::Visual,
+                Key::Char('v'),
+                none,
+                vec![Command::SwitchMode(Mode::Normal)],
+            )
+            .bind(
+                Mode::Visual,
+                Key::Char('d'),
+                none,
+                vec![Command::DeleteSelection],
+            )
+            .bind(
+                Mode::Visual,
+                Key::Char('x'),
+                none,
+                vec![Command::DeleteSelection],
+            )
+            .bind(
+                Mode::Visual,
+                Key::Char('y'),
+                none,
+                vec![Command::YankSelection],
+            )
+            .bind(
+                Mode::Visual,
+                Key::Char('U'),
+                none,
+                vec![Command::TransformCase(CaseTransform::Upper)],
+            )
+            .bind(
+                Mode::Visual,
+                Key::Char('u'),
+                none,
+                vec![Command::TransformCase(CaseTransform::Lower)],
+            )
+            .bind(
+                Mode::Visual,
+                Key::Char('~'),
+                none,
+                vec![Command::TransformCase(CaseTransform::Toggle)],
+            )
+            .bind(
+                Mode::Visual,
+                Key::Char('>'),
+                none,
+                vec![Command::IndentSelection],
+            )
+            .bind(
+                Mode::Visual,
+                Key::Char('<'),
+                none,
+                vec![Command::OutdentSelection],
+            );
+
+        // Typing a pair opener in visual mode wraps the selection.
+        for open in ['(', '[', '{', '"', '\''] {
+            keymap.bind(
+                Mode::Visual,
+                Key::Char(open),
+                none,
+                vec![Command::SurroundSelection(open)],
+            );
+        }
+
+        // Search prompt.
+        keymap
+            .bind(Mode::Search, Key::Esc, none, vec![Command::SearchCancel])
+            .bind(
+                Mode::Search,
+                Key::Enter,
+                none,
+                vec![Command::SearchSubmit],
+            )
+            .bind(
+                Mode::Search,
+                Key::Backspace,
+                none,
+                vec![Command::SearchBackspace],
+            );
+
+        // Command prompt.
+        keymap
+            .bind(
+                Mode::Command,
+                Key::Esc,
+                none,
+                vec![Command::CommandCancel],
+            )
+            .bind(
+                Mode::Command,
+                Key::Enter,
+                none,
+                vec![Command::CommandSubmit],
+            )
+            .bind(
+                Mode::Command,
+                Key::Backspace,
+                none,
+                vec![Command::CommandBackspace],
+            );
+
+        // Insert mode.
+        keymap
+            .bind(
+                Mode::Insert,
+                Key::Esc,
+                none,
+                vec![Command::MoveCursorLeft, Command::SwitchMode(Mode::Normal)],
+            )
+            .bind(
+                Mode::Insert,
+                Key::Enter,
+                none,
+                vec![Command::InsertChar('\n')],
+            )
+            .bind(
+                Mode::Insert,
+                Key::Left,
+                none,
+                vec![Command::MoveCursorLeft],
+            )
+            .bind(
+                Mode::Insert,
+                Key::Right,
+                none,
+                vec![Command::MoveCursorRight(false)],
+            )
+            .bind(Mode::Insert, Key::Up, none, vec![Command::MoveCursorUp])
+            .bind(
+                Mode::Insert,
+                Key::Down,
+                none,
+                vec![Command::MoveCursorDown],
+            )
+            .bind(
+                Mode::Insert,
+                Key::Backspace,
+                none,
+                vec![Command::DeleteCharBackward],
+            )
+            .bind(
+                Mode::Insert,
+                Key::Delete,
+                none,
+                vec![Command::DeleteCharForward],
+            );
+
+        keymap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(key: Key, modifiers: Modifiers) -> KeyPress {
+        KeyPress { key, modifiers }
+    }
+
+    #[test]
+    fn bind_and_get_round_trip() {
+        let mut keymap = Keymap::empty();
+        keymap.bind(
+            Mode::Normal,
+            Key::Char('q'),
+            Modifiers::NONE,
+            vec![Command::Quit],
+        );
+
+        let commands = keymap
+            .get(Mode::Normal, &press(Key::Char('q'), Modifiers::NONE))
+            .expect("the binding to resolve");
+        assert!(matches!(commands, [Command::Quit]));
+
+        // The same key in another mode, or with a modifier, is unbound.
+        assert!(keymap
+            .get(Mode::Insert, &press(Key::Char('q'), Modifiers::NONE))
+            .is_none());
+        assert!(keymap
+            .get(Mode::Normal, &press(Key::Char('q'), Modifiers::CTRL))
+            .is_none());
+    }
+
+    #[test]
+    fn shift_is_normalized_away_for_characters() {
+        let keymap = Keymap::default_bindings();
+
+        // Terminals report `W` both as a plain char and as shift+char;
+        // both must resolve to the same binding.
+        let shifted = Modifiers {
+            shift: true,
+            ..Modifiers::NONE
+        };
+        assert!(matches!(
+            keymap.get(Mode::Normal, &press(Key::Char('W'), shifted)),
+            Some([Command::MoveCursorWordForward(true)])
+        ));
+    }
+
+    #[test]
+    fn merge_overrides_only_the_layered_keys() {
+        let mut overrides = Keymap::empty();
+        overrides.bind(
+            Mode::Normal,
+            Key::Char('q'),
+            Modifiers::NONE,
+            vec![Command::None],
+        );
+
+        let keymap = Keymap::layered(overrides);
+
+        // The override wins where both bind the same key...
+        assert!(matches!(
+            keymap.get(Mode::Normal, &press(Key::Char('q'), Modifiers::NONE)),
+            Some([Command::None])
+        ));
+        // ...and everything else falls through to the defaults.
+        assert!(matches!(
+            keymap.get(Mode::Normal, &press(Key::Char('u'), Modifiers::NONE)),
+            Some([Command::Undo])
+        ));
+    }
+
+    #[test]
+    fn default_bindings_cover_the_core_vim_keys() {
+        let keymap = Keymap::default_bindings();
+
+        assert!(matches!(
+            keymap.get(Mode::Normal, &press(Key::Char('j'), Modifiers::NONE)),
+            Some([Command::MoveCursorDown])
+        ));
+        assert!(matches!(
+            keymap.get(Mode::Normal, &press(Key::Char('d'), Modifiers::CTRL)),
+            Some([Command::HalfPageDown])
+        ));
+        assert!(matches!(
+            keymap.get(Mode::Visual, &press(Key::Char('y'), Modifiers::NONE)),
+            Some([Command::YankSelection])
+        ));
+        assert!(matches!(
+            keymap.get(Mode::Insert, &press(Key::Esc, Modifiers::NONE)),
+            Some([Command::MoveCursorLeft, Command::SwitchMode(Mode::Normal)])
+        ));
+    }
+}
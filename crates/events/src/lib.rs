@@ -1,9 +1,13 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crossterm::event::{self, Event as CEvent, KeyCode, KeyEvent};
 use thiserror::Error;
 use utils::{Command, Mode, Size};
 
+pub use key_repeat::KeyRepeat;
+pub use keymap::Keymap;
+mod key_repeat;
+mod keymap;
+
 /// Represents all possible errors that can occur in `events`.
 #[derive(Error, Debug)]
 pub enum EventsError {
@@ -19,35 +23,154 @@ pub enum EventsError {
     GenericError(String),
 }
 
+/// A key on the keyboard, decoupled from any terminal backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Esc,
+    Backspace,
+    Delete,
+    Tab,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+}
+
+/// The modifier keys held during a key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Self = Self {
+        ctrl: false,
+        alt: false,
+        shift: false,
+    };
+
+    pub const CTRL: Self = Self {
+        ctrl: true,
+        alt: false,
+        shift: false,
+    };
+
+    pub const ALT: Self = Self {
+        ctrl: false,
+        alt: true,
+        shift: false,
+    };
+}
+
+/// A key press: which key, and which modifiers were held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPress {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+/// A mouse button, decoupled from any terminal backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
 /// Event is any type of event that the editor can compute.
+/// Mouse events carry the cell coordinates `(x, y)` they happened at.
 pub enum Event {
-    KeyPress(KeyEvent),
+    KeyPress(KeyPress),
     Resize(usize, usize),
-    Mock, // TODO: more events like mouse clicking, scrolling, and things of the nature.
+    MouseDown(MouseButton, usize, usize),
+    MouseUp(MouseButton, usize, usize),
+    MouseDrag(MouseButton, usize, usize),
+    Scroll(isize), // Positive scrolls the view down.
+    Paste(String), // A bracketed paste: the whole block in one event.
+    Mock,
 }
 
-pub struct EventHandler;
+/// Where events come from. The crossterm-backed `Terminal` in `renderer`
+/// implements this for the real editor; tests can implement it to feed
+/// synthetic events through the handler.
+pub trait EventSource {
+    /// Returns the next pending event, or `None` when nothing is waiting.
+    /// Must not block.
+    fn next_event(&mut self) -> Result<Option<Event>, EventsError>;
+}
+
+pub struct EventHandler {
+    keymap: Keymap,
+    key_repeat: Option<KeyRepeat>,
+}
 
 impl EventHandler {
     pub fn new() -> Self {
-        EventHandler
+        EventHandler {
+            keymap: Keymap::default_bindings(),
+            key_repeat: None,
+        }
     }
 
-    /// Capture events from the terminal and return them in a Vector.
-    pub fn poll_events(&self) -> Result<Vec<Event>, EventsError> {
+    /// An event handler with a custom keymap, so `app` can apply user
+    /// overrides on top of (or instead of) the defaults.
+    pub fn with_keymap(keymap: Keymap) -> Self {
+        EventHandler {
+            keymap,
+            key_repeat: None,
+        }
+    }
+
+    /// Enables synthesized key repeats for held keys. Off by default,
+    /// since most terminals auto-repeat held keys on their own; useful
+    /// where that repeat is disabled or too slow.
+    pub fn with_key_repeat(mut self, initial_delay: Duration, repeat_interval: Duration) -> Self {
+        self.key_repeat = Some(KeyRepeat::new(initial_delay, repeat_interval));
+        self
+    }
+
+    /// Captures everything the source has pending and returns it in a
+    /// Vector. Resize events are coalesced into a single one with the
+    /// final dimensions, since tiling window managers emit them in bursts
+    /// and only the last size matters. With key repeat enabled, an idle
+    /// poll may synthesize a repeat press of the held key instead.
+    pub fn poll_events(
+        &mut self,
+        source: &mut impl EventSource,
+    ) -> Result<Vec<Event>, EventsError> {
         let mut events = Vec::new();
+        let mut resize: Option<(usize, usize)> = None;
 
-        // We use event::poll here with a timeout of 0 to make it non-blocking.
-        if event::poll(Duration::from_millis(0))? {
-            if let Ok(c_event) = event::read() {
-                // c_event is a crossterm event.
-                match c_event {
-                    CEvent::Key(key_event) => events.push(Event::KeyPress(key_event)),
-                    CEvent::Resize(width, height) => {
-                        events.push(Event::Resize(width as usize, height as usize))
+        while let Some(event) = source.next_event()? {
+            match event {
+                Event::Resize(width, height) => resize = Some((width, height)),
+                Event::KeyPress(key_press) => {
+                    if let Some(repeat) = &mut self.key_repeat {
+                        repeat.key_pressed(key_press, Instant::now());
                     }
-                    // TODO: Treat other events.
-                    _ => {}
+
+                    events.push(Event::KeyPress(key_press));
+                }
+                other => events.push(other),
+            }
+        }
+
+        if let Some((width, height)) = resize {
+            events.push(Event::Resize(width, height));
+        }
+
+        if events.is_empty() {
+            if let Some(repeat) = &mut self.key_repeat {
+                if let Some(key_press) = repeat.poll_repeat(Instant::now()) {
+                    events.push(Event::KeyPress(key_press));
                 }
             }
         }
@@ -55,73 +178,246 @@ impl EventHandler {
         Ok(events)
     }
 
-    /// Maps `Events` from `crossterm` to a `Vec<Command>`
+    /// Maps an `Event` to a `Vec<Command>`
     pub fn handle_event(&self, event: Event, mode: Mode) -> Result<Vec<Command>, EventsError> {
         let mut commands = Vec::new();
 
         match event {
-            Event::KeyPress(key_event) => {
+            Event::KeyPress(key_press) => {
                 // Reuse the existing logic to `KeyPress`
-                commands = self.handle_key_event(key_event, mode)?;
+                commands = self.handle_key_event(key_press, mode)?;
             }
             Event::Resize(width, height) => {
                 commands.push(Command::Resize(Size { width, height }));
             }
+            // Clicking or dragging with the left button moves the cursor;
+            // the other buttons do nothing for now.
+            Event::MouseDown(MouseButton::Left, x, y) | Event::MouseDrag(MouseButton::Left, x, y) => {
+                commands.push(Command::SetCursorAtScreen(x, y));
+            }
+            Event::MouseDown(..) | Event::MouseUp(..) | Event::MouseDrag(..) => {}
+            Event::Scroll(amount) => {
+                // A wheel notch moves the view by three lines, like most
+                // terminal programs.
+                if amount < 0 {
+                    commands.push(Command::ScrollUp(3));
+                } else {
+                    commands.push(Command::ScrollDown(3));
+                }
+            }
+            Event::Paste(text) => match mode {
+                // The prompts are single-line, so a paste feeds them one
+                // character at a time and drops any line breaks.
+                Mode::Search => commands.extend(
+                    text.chars()
+                        .filter(|c| *c != '\n' && *c != '\r')
+                        .map(Command::SearchInput),
+                ),
+                Mode::Command => commands.extend(
+                    text.chars()
+                        .filter(|c| *c != '\n' && *c != '\r')
+                        .map(Command::CommandInput),
+                ),
+                // Elsewhere, one command for the whole block, so pasting
+                // skips the per-character insert path.
+                _ => commands.push(Command::InsertText(text)),
+            },
             Event::Mock => {}
         }
 
         Ok(commands)
     }
 
-    /// Returns a `Vec<Command>` based on the current `Mode` and `KeyEvent`.
+    /// Returns a `Vec<Command>` based on the current `Mode` and `KeyPress`,
+    /// by looking the key up in the keymap.
     pub fn handle_key_event(
         &self,
-        key_event: KeyEvent,
+        key_press: KeyPress,
         mode: Mode,
     ) -> Result<Vec<Command>, EventsError> {
-        let mut commands = Vec::new();
-
-        match mode {
-            Mode::Normal => match key_event.code {
-                KeyCode::Char('q') => commands.push(Command::Quit),
-                KeyCode::Char('h') => commands.push(Command::MoveCursorLeft),
-                KeyCode::Char('l') => commands.push(Command::MoveCursorRight(false)),
-                KeyCode::Char('k') => commands.push(Command::MoveCursorUp),
-                KeyCode::Char('j') => commands.push(Command::MoveCursorDown),
-                KeyCode::Char('i') => commands.push(Command::SwitchMode(Mode::Insert)),
-                KeyCode::Char('$') => commands.push(Command::MoveCursorEndOfLine),
-                KeyCode::Char('0') => commands.push(Command::MoveCursorStartOfLine),
-                KeyCode::Char('_') => commands.push(Command::MoveCursorFirstCharOfLine),
-                KeyCode::Char('w') => commands.push(Command::MoveCursorWordForward(false)),
-                KeyCode::Char('W') => commands.push(Command::MoveCursorWordForward(true)),
-                KeyCode::Char('b') => commands.push(Command::MoveCursorWordBackward(false)),
-                KeyCode::Char('B') => commands.push(Command::MoveCursorWordBackward(true)),
-                KeyCode::Char('e') => commands.push(Command::MoveCursorWordForwardEnd(false)),
-                KeyCode::Char('E') => commands.push(Command::MoveCursorWordForwardEnd(true)),
-                KeyCode::Char('x') => commands.push(Command::DeleteCharForward),
-                KeyCode::Char('a') => {
-                    commands.push(Command::MoveCursorRight(true));
-                    commands.push(Command::SwitchMode(Mode::Insert));
-                }
-                _ => {}
-            },
-            Mode::Insert => match key_event.code {
-                KeyCode::Esc => {
-                    commands.push(Command::MoveCursorLeft);
-                    commands.push(Command::SwitchMode(Mode::Normal))
-                },
-                KeyCode::Char(c) => commands.push(Command::InsertChar(c)),
-                KeyCode::Enter => commands.push(Command::InsertChar('\n')),
-                KeyCode::Left => commands.push(Command::MoveCursorLeft),
-                KeyCode::Right => commands.push(Command::MoveCursorRight(false)),
-                KeyCode::Up => commands.push(Command::MoveCursorUp),
-                KeyCode::Down => commands.push(Command::MoveCursorDown),
-                KeyCode::Backspace => commands.push(Command::DeleteCharBackward),
-                KeyCode::Delete => commands.push(Command::DeleteCharForward),
-                _ => {}
-            },
+        if let Some(commands) = self.keymap.get(mode, &key_press) {
+            return Ok(commands.to_vec());
         }
 
+        // Arbitrary character input cannot be enumerated in the keymap, so
+        // the text-entry modes fall back to it here.
+        let commands = match (mode, key_press.key) {
+            (Mode::Insert, Key::Char(c)) => vec![Command::InsertChar(c)],
+            (Mode::Search, Key::Char(c)) => vec![Command::SearchInput(c)],
+            (Mode::Command, Key::Char(c)) => vec![Command::CommandInput(c)],
+            _ => Vec::new(),
+        };
+
         Ok(commands)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An event source backed by a queue of pre-baked events.
+    struct MockSource {
+        events: Vec<Event>,
+    }
+
+    impl MockSource {
+        fn new(events: Vec<Event>) -> Self {
+            // Popping off the back keeps delivery in push order.
+            let mut events = events;
+            events.reverse();
+            Self { events }
+        }
+    }
+
+    impl EventSource for MockSource {
+        fn next_event(&mut self) -> Result<Option<Event>, EventsError> {
+            Ok(self.events.pop())
+        }
+    }
+
+    fn press(c: char) -> Event {
+        Event::KeyPress(KeyPress {
+            key: Key::Char(c),
+            modifiers: Modifiers::NONE,
+        })
+    }
+
+    #[test]
+    fn a_resize_burst_coalesces_to_the_final_size() {
+        let mut handler = EventHandler::new();
+        let mut source = MockSource::new(vec![
+            Event::Resize(10, 10),
+            Event::Resize(20, 20),
+            Event::Resize(80, 24),
+        ]);
+
+        let events = handler.poll_events(&mut source).expect("polling to work");
+
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], Event::Resize(80, 24)));
+    }
+
+    #[test]
+    fn key_presses_survive_resize_coalescing() {
+        let mut handler = EventHandler::new();
+        let mut source = MockSource::new(vec![
+            Event::Resize(10, 10),
+            press('j'),
+            Event::Resize(80, 24),
+        ]);
+
+        let events = handler.poll_events(&mut source).expect("polling to work");
+
+        // The key press comes through, and the single resize lands after
+        // it so the final size wins.
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            events[0],
+            Event::KeyPress(KeyPress {
+                key: Key::Char('j'),
+                ..
+            })
+        ));
+        assert!(matches!(events[1], Event::Resize(80, 24)));
+    }
+
+    #[test]
+    fn an_empty_source_polls_to_no_events() {
+        let mut handler = EventHandler::new();
+        let mut source = MockSource::new(Vec::new());
+
+        assert!(handler
+            .poll_events(&mut source)
+            .expect("polling to work")
+            .is_empty());
+    }
+
+    #[test]
+    fn a_paste_is_one_insert_outside_the_prompts() {
+        let handler = EventHandler::new();
+
+        // A multi-line paste stays a single edit.
+        let commands = handler
+            .handle_event(Event::Paste("one\ntwo\n".to_string()), Mode::Insert)
+            .expect("handling to work");
+        assert_eq!(commands.len(), 1);
+        assert!(matches!(&commands[0], Command::InsertText(text) if text == "one\ntwo\n"));
+    }
+
+    #[test]
+    fn a_paste_feeds_the_prompts_per_character() {
+        let handler = EventHandler::new();
+
+        let commands = handler
+            .handle_event(Event::Paste("ab\nc".to_string()), Mode::Search)
+            .expect("handling to work");
+        // Line breaks are dropped: the prompts are single-line.
+        assert!(matches!(
+            commands[..],
+            [
+                Command::SearchInput('a'),
+                Command::SearchInput('b'),
+                Command::SearchInput('c'),
+            ]
+        ));
+
+        let commands = handler
+            .handle_event(Event::Paste("wq".to_string()), Mode::Command)
+            .expect("handling to work");
+        assert!(matches!(
+            commands[..],
+            [Command::CommandInput('w'), Command::CommandInput('q')]
+        ));
+    }
+
+    #[test]
+    fn left_clicks_and_drags_move_the_cursor() {
+        let handler = EventHandler::new();
+
+        let commands = handler
+            .handle_event(Event::MouseDown(MouseButton::Left, 5, 7), Mode::Normal)
+            .expect("handling to work");
+        assert!(matches!(commands[..], [Command::SetCursorAtScreen(5, 7)]));
+
+        let commands = handler
+            .handle_event(Event::MouseDrag(MouseButton::Left, 2, 3), Mode::Normal)
+            .expect("handling to work");
+        assert!(matches!(commands[..], [Command::SetCursorAtScreen(2, 3)]));
+    }
+
+    #[test]
+    fn the_other_mouse_buttons_do_nothing() {
+        let handler = EventHandler::new();
+
+        for event in [
+            Event::MouseDown(MouseButton::Right, 1, 1),
+            Event::MouseDown(MouseButton::Middle, 1, 1),
+            Event::MouseUp(MouseButton::Left, 1, 1),
+        ] {
+            let commands = handler
+                .handle_event(event, Mode::Normal)
+                .expect("handling to work");
+            assert!(commands.is_empty());
+        }
+    }
+
+    #[test]
+    fn unbound_keys_fall_back_to_text_input_per_mode() {
+        let handler = EventHandler::new();
+        let key_press = KeyPress {
+            key: Key::Char('x'),
+            modifiers: Modifiers::NONE,
+        };
+
+        let commands = handler
+            .handle_key_event(key_press, Mode::Insert)
+            .expect("handling to work");
+        assert!(matches!(commands[..], [Command::InsertChar('x')]));
+
+        let commands = handler
+            .handle_key_event(key_press, Mode::Search)
+            .expect("handling to work");
+        assert!(matches!(commands[..], [Command::SearchInput('x')]));
+    }
+}
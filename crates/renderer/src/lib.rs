@@ -1,6 +1,7 @@
 use terminal::TerminalInterface;
 use text_engine::Rope;
 use thiserror::Error;
+use unicode_width::UnicodeWidthChar;
 pub mod terminal;
 
 /// `Component` is like a React component, but for the-editor.
@@ -11,18 +12,44 @@ pub trait Component {
         T: TerminalInterface;
 }
 
+/// A drawing color. The named variants cover the editor defaults, while
+/// `Rgb` and `Indexed` (256-color palette) open the door for syntax
+/// highlighting themes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Reset, // The terminal's default color.
+    Black,
+    White,
+    LightGray,
+    Blue,
+    Rgb(u8, u8, u8),
+    Indexed(u8),
+}
+
+/// The shape the terminal cursor takes, switched per editor mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Block,
+    Bar,
+    Underline,
+}
+
 /// Represents all commands that can be queued to be rendered.
 #[derive(Debug, Clone)]
 pub enum TerminalCommand {
     ClearScreen,
     Print(String),
     PrintRope(Rope),
+    PrintStyled(Vec<(String, Color)>), // A line drawn as per-color runs.
     MoveCursor(usize, usize),
     HideCursor,
     ShowCursor,
-    ChangeCursorStyleBlock,
-    ChangeCursorStyleBar,
+    SetCursorShape(CursorShape),
     ClearLine,
+    EnableReverseVideo,
+    DisableReverseVideo,
+    SetForegroundColor(Color),
+    ResetColor,
 }
 
 /// Represents all possible errors that can occur in `renderer`.
@@ -36,11 +63,61 @@ pub enum RendererError {
     GenericError(String),
 }
 
+/// A single screen cell of the double buffer. The symbol is a string,
+/// not a char: a cell holds a full grapheme cluster, so combining marks
+/// ride along with their base character.
+#[derive(Clone, PartialEq, Eq)]
+struct Cell {
+    symbol: String,
+    color: Color,
+    reverse: bool,
+}
+
+impl Cell {
+    fn blank() -> Self {
+        Cell {
+            symbol: " ".to_string(),
+            color: Color::Reset,
+            reverse: false,
+        }
+    }
+
+    /// The trailing half of a double-width glyph. It holds no symbol of its
+    /// own: the terminal draws it as part of the wide character to its left.
+    fn continuation(color: Color, reverse: bool) -> Self {
+        Cell {
+            symbol: "\0".to_string(),
+            color,
+            reverse,
+        }
+    }
+}
+
+/// The drawing state while interpreting queued commands: where the next
+/// character lands and with which attributes.
+struct Pen {
+    x: usize,
+    y: usize,
+    color: Color,
+    reverse: bool,
+    last_glyph: Option<usize>, // Cell a following combining mark attaches to.
+}
+
 /// Renderer is responsible for rendering the state of the editor in the terminal.
+///
+/// Drawing commands are interpreted into a back buffer of cells, which is
+/// diffed against what is already on screen on every `render` call, so only
+/// the cells that actually changed are rewritten. This kills the flicker
+/// the old clear-and-redraw path caused on slow terminals.
 #[derive(Clone)]
 pub struct Renderer<T: TerminalInterface> {
     terminal: T,
     command_queue: Vec<TerminalCommand>,
+    front: Vec<Cell>, // What is currently on screen.
+    back: Vec<Cell>,  // What the queued commands produce.
+    width: usize,
+    height: usize,
+    force_full_redraw: bool,
 }
 
 impl<T: TerminalInterface> Renderer<T> {
@@ -48,6 +125,11 @@ impl<T: TerminalInterface> Renderer<T> {
         Renderer {
             terminal,
             command_queue: Vec::new(),
+            front: Vec::new(),
+            back: Vec::new(),
+            width: 0,
+            height: 0,
+            force_full_redraw: false,
         }
     }
 
@@ -56,15 +138,361 @@ impl<T: TerminalInterface> Renderer<T> {
         self.command_queue.push(command)
     }
 
-    /// Renders all enqueued commands.
+    /// The terminal backend, e.g. for polling it as an event source.
+    pub fn terminal_mut(&mut self) -> &mut T {
+        &mut self.terminal
+    }
+
+    /// Resizes the double buffer, which invalidates everything on screen.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.front = vec![Cell::blank(); width * height];
+        self.back = vec![Cell::blank(); width * height];
+        self.force_full_redraw = true;
+    }
+
+    /// Forces the next `render` to rewrite the whole screen, e.g. after a
+    /// resize garbled what we believe is on screen.
+    pub fn force_redraw(&mut self) {
+        self.force_full_redraw = true;
+    }
+
+    /// Renders all enqueued commands, flushing only the cells that differ
+    /// from the previous frame.
     pub fn render(&mut self) -> Result<(), RendererError> {
-        for command in &self.command_queue {
-            self.terminal.queue(command.clone())?;
+        let commands = std::mem::take(&mut self.command_queue);
+
+        let mut pen = Pen {
+            x: 0,
+            y: 0,
+            color: Color::Reset,
+            reverse: false,
+            last_glyph: None,
+        };
+        let mut cursor = (0, 0);
+        let mut passthrough = Vec::new();
+
+        for command in commands {
+            match command {
+                TerminalCommand::MoveCursor(x, y) => {
+                    pen.x = x;
+                    pen.y = y;
+                    pen.last_glyph = None;
+                    cursor = (x, y);
+                }
+                TerminalCommand::Print(text) => self.write_text(&mut pen, &text),
+                TerminalCommand::PrintRope(rope) => {
+                    for chunk in rope.chunks() {
+                        self.write_text(&mut pen, chunk);
+                    }
+                }
+                TerminalCommand::PrintStyled(runs) => {
+                    for (text, color) in runs {
+                        pen.color = color;
+                        self.write_text(&mut pen, &text);
+                    }
+                    pen.color = Color::Reset;
+                }
+                TerminalCommand::ClearScreen => self.back.fill(Cell::blank()),
+                TerminalCommand::ClearLine => {
+                    if pen.y < self.height {
+                        let start = pen.y * self.width;
+                        self.back[start..start + self.width].fill(Cell::blank());
+                    }
+                }
+                TerminalCommand::EnableReverseVideo => pen.reverse = true,
+                TerminalCommand::DisableReverseVideo => pen.reverse = false,
+                TerminalCommand::SetForegroundColor(color) => pen.color = color,
+                TerminalCommand::ResetColor => pen.color = Color::Reset,
+                // Cursor visibility/shape are not cell contents; forward
+                // them to the terminal untouched.
+                other => passthrough.push(other),
+            }
+        }
+
+        for command in passthrough {
+            self.terminal.queue(command)?;
+        }
+
+        if self.force_full_redraw {
+            // Everything we believe about the screen is stale.
+            self.terminal.queue(TerminalCommand::ClearScreen)?;
+            self.front.fill(Cell::blank());
+            self.force_full_redraw = false;
         }
 
+        self.flush_diff()?;
+        self.front.clone_from(&self.back);
+
+        // Finally, park the terminal cursor wherever the last MoveCursor
+        // asked for (that's the text cursor).
+        self.terminal
+            .queue(TerminalCommand::MoveCursor(cursor.0, cursor.1))?;
         self.terminal.flush()?;
-        self.command_queue.clear();
 
         Ok(())
     }
+
+    /// Writes a string into the back buffer, advancing the pen by the
+    /// display width of each glyph: CJK and most emoji take two cells,
+    /// zero-width characters (e.g. combining marks) take none and attach
+    /// to the glyph before them. Writes outside the buffer and control
+    /// characters are ignored.
+    fn write_text(&mut self, pen: &mut Pen, text: &str) {
+        for c in text.chars() {
+            if c == '\n' || c == '\r' {
+                continue;
+            }
+
+            let glyph_width = c.width().unwrap_or(0);
+            if glyph_width == 0 {
+                // A combining mark joins the cell of its base character,
+                // so "e" + U+0301 renders as one accented glyph.
+                if let Some(idx) = pen.last_glyph {
+                    self.back[idx].symbol.push(c);
+                }
+                continue;
+            }
+
+            if pen.x + glyph_width <= self.width && pen.y < self.height {
+                let idx = pen.y * self.width + pen.x;
+                self.back[idx] = Cell {
+                    symbol: c.to_string(),
+                    color: pen.color,
+                    reverse: pen.reverse,
+                };
+                pen.last_glyph = Some(idx);
+
+                if glyph_width == 2 {
+                    self.back[idx + 1] = Cell::continuation(pen.color, pen.reverse);
+                }
+            } else if pen.x < self.width && pen.y < self.height {
+                // A wide glyph that would spill past the right edge: blank
+                // the half-cell instead of letting the terminal wrap.
+                self.back[pen.y * self.width + pen.x] = Cell::blank();
+                pen.last_glyph = None;
+            }
+
+            pen.x += glyph_width;
+        }
+    }
+
+    /// Emits the difference between the two buffers as runs of changed
+    /// cells sharing the same attributes.
+    fn flush_diff(&self) -> Result<(), RendererError> {
+        for y in 0..self.height {
+            let mut x = 0;
+
+            while x < self.width {
+                let idx = y * self.width + x;
+
+                if self.back[idx] == self.front[idx] {
+                    x += 1;
+                    continue;
+                }
+
+                // Extend the run while cells changed and share attributes.
+                let color = self.back[idx].color;
+                let reverse = self.back[idx].reverse;
+                let mut end = x;
+                let mut text = String::new();
+
+                while end < self.width {
+                    let idx = y * self.width + end;
+                    let cell = &self.back[idx];
+
+                    if *cell == self.front[idx] || cell.color != color || cell.reverse != reverse
+                    {
+                        break;
+                    }
+
+                    // Continuation cells carry no glyph: the wide character
+                    // before them already covers this column on screen.
+                    if cell.symbol != "\0" {
+                        text.push_str(&cell.symbol);
+                    }
+                    end += 1;
+                }
+
+                self.terminal.queue(TerminalCommand::MoveCursor(x, y))?;
+                if reverse {
+                    self.terminal.queue(TerminalCommand::EnableReverseVideo)?;
+                }
+                self.terminal
+                    .queue(TerminalCommand::SetForegroundColor(color))?;
+                self.terminal.queue(TerminalCommand::Print(text))?;
+                if reverse {
+                    self.terminal.queue(TerminalCommand::DisableReverseVideo)?;
+                }
+                self.terminal.queue(TerminalCommand::ResetColor)?;
+
+                x = end;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    /// A terminal that records what gets queued instead of drawing, so a
+    /// test can assert on exactly which cells the diff rewrites.
+    struct MockTerminal {
+        queued: RefCell<Vec<TerminalCommand>>,
+    }
+
+    impl MockTerminal {
+        fn new() -> Self {
+            Self {
+                queued: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl TerminalInterface for MockTerminal {
+        fn init() -> Result<(), RendererError> {
+            Ok(())
+        }
+
+        fn queue(&self, command: TerminalCommand) -> Result<(), RendererError> {
+            self.queued.borrow_mut().push(command);
+            Ok(())
+        }
+
+        fn flush(&self) -> Result<(), RendererError> {
+            Ok(())
+        }
+
+        fn kill() -> Result<(), RendererError> {
+            Ok(())
+        }
+
+        fn size() -> Result<(usize, usize), RendererError> {
+            Ok((80, 24))
+        }
+    }
+
+    /// Drains the strings printed since the last call, dropping the runs
+    /// of blanks the full redraw after a resize produces.
+    fn printed(renderer: &mut Renderer<MockTerminal>) -> Vec<String> {
+        renderer
+            .terminal_mut()
+            .queued
+            .get_mut()
+            .drain(..)
+            .filter_map(|command| match command {
+                TerminalCommand::Print(text) if !text.trim().is_empty() => Some(text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_frames_print_nothing() {
+        let mut renderer = Renderer::new(MockTerminal::new());
+        renderer.resize(4, 1);
+
+        for frame in 0..2 {
+            renderer.enqueue_command(TerminalCommand::MoveCursor(0, 0));
+            renderer.enqueue_command(TerminalCommand::Print("hi".to_string()));
+            renderer.render().expect("render to succeed");
+
+            let output = printed(&mut renderer);
+            if frame == 0 {
+                assert_eq!(output, vec!["hi".to_string()]);
+            } else {
+                assert!(output.is_empty(), "second frame reprinted {output:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn only_the_changed_cells_are_rewritten() {
+        let mut renderer = Renderer::new(MockTerminal::new());
+        renderer.resize(4, 1);
+
+        renderer.enqueue_command(TerminalCommand::MoveCursor(0, 0));
+        renderer.enqueue_command(TerminalCommand::Print("hi".to_string()));
+        renderer.render().expect("render to succeed");
+        printed(&mut renderer);
+
+        renderer.enqueue_command(TerminalCommand::MoveCursor(0, 0));
+        renderer.enqueue_command(TerminalCommand::Print("ha".to_string()));
+        renderer.render().expect("render to succeed");
+
+        // Only the second column differs between the frames.
+        assert_eq!(printed(&mut renderer), vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn combining_marks_stay_with_their_base_cell() {
+        let mut renderer = Renderer::new(MockTerminal::new());
+        renderer.resize(4, 1);
+
+        renderer.enqueue_command(TerminalCommand::MoveCursor(0, 0));
+        renderer.enqueue_command(TerminalCommand::Print("e\u{301}x".to_string()));
+        renderer.render().expect("render to succeed");
+
+        // The accent rides along in the base character's cell instead of
+        // being dropped, and takes no column of its own.
+        assert_eq!(
+            printed(&mut renderer),
+            vec!["e\u{301}x".to_string()]
+        );
+        assert_eq!(renderer.front[0].symbol, "e\u{301}");
+        assert_eq!(renderer.front[1].symbol, "x");
+    }
+
+    #[test]
+    fn wide_glyphs_take_two_cells_but_print_once() {
+        let mut renderer = Renderer::new(MockTerminal::new());
+        renderer.resize(4, 1);
+
+        renderer.enqueue_command(TerminalCommand::MoveCursor(0, 0));
+        renderer.enqueue_command(TerminalCommand::Print("你a".to_string()));
+        renderer.render().expect("render to succeed");
+
+        // The continuation half-cell holds no glyph, so the printed run
+        // must not duplicate or pad the wide character.
+        assert_eq!(printed(&mut renderer), vec!["你a".to_string()]);
+        assert_eq!(renderer.front[1].symbol, "\0");
+        assert_eq!(renderer.front[2].symbol, "a");
+    }
+
+    #[test]
+    fn wide_glyph_at_the_right_edge_is_blanked() {
+        let mut renderer = Renderer::new(MockTerminal::new());
+        renderer.resize(2, 1);
+
+        renderer.enqueue_command(TerminalCommand::MoveCursor(1, 0));
+        renderer.enqueue_command(TerminalCommand::Print("你".to_string()));
+        renderer.render().expect("render to succeed");
+
+        // Half of the glyph would spill past the edge: nothing is drawn.
+        assert!(printed(&mut renderer).is_empty());
+    }
+
+    #[test]
+    fn force_redraw_invalidates_the_front_buffer() {
+        let mut renderer = Renderer::new(MockTerminal::new());
+        renderer.resize(4, 1);
+
+        renderer.enqueue_command(TerminalCommand::MoveCursor(0, 0));
+        renderer.enqueue_command(TerminalCommand::Print("hi".to_string()));
+        renderer.render().expect("render to succeed");
+        printed(&mut renderer);
+
+        renderer.force_redraw();
+        renderer.enqueue_command(TerminalCommand::MoveCursor(0, 0));
+        renderer.enqueue_command(TerminalCommand::Print("hi".to_string()));
+        renderer.render().expect("render to succeed");
+
+        assert_eq!(printed(&mut renderer), vec!["hi".to_string()]);
+    }
 }
@@ -1,17 +1,100 @@
-use std::io::{stdout, Write};
+use std::{
+    io::{stdout, Write},
+    time::Duration,
+};
 
 use crossterm::{
     cursor::{Hide, MoveTo, SetCursorStyle, Show},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste,
+        EnableMouseCapture, Event as CEvent, KeyCode, KeyEvent as CKeyEvent, KeyEventKind,
+        MouseButton as CMouseButton, MouseEvent, MouseEventKind,
+    },
     execute, queue,
-    style::Print,
+    style::{Attribute, Color as CColor, Print, ResetColor, SetAttribute, SetForegroundColor},
     terminal::{
         disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen,
         LeaveAlternateScreen,
     },
     Command as CECommand,
 };
+use events::{Event, EventSource, EventsError, Key, KeyPress, Modifiers, MouseButton};
+
+use crate::{Color, CursorShape, RendererError, TerminalCommand};
+
+/// Maps a crossterm key event to our backend-agnostic `KeyPress`, dropping
+/// presses of keys we don't model (and key releases on platforms that
+/// report them).
+fn map_key_event(key_event: CKeyEvent) -> Option<KeyPress> {
+    if key_event.kind != KeyEventKind::Press {
+        return None;
+    }
+
+    let key = match key_event.code {
+        KeyCode::Char(c) => Key::Char(c),
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        _ => return None,
+    };
+
+    let modifiers = Modifiers {
+        ctrl: key_event
+            .modifiers
+            .contains(event::KeyModifiers::CONTROL),
+        alt: key_event.modifiers.contains(event::KeyModifiers::ALT),
+        shift: key_event.modifiers.contains(event::KeyModifiers::SHIFT),
+    };
+
+    Some(KeyPress { key, modifiers })
+}
 
-use crate::{RendererError, TerminalCommand};
+/// Maps a crossterm mouse event to one of ours, dropping the kinds we
+/// don't care about (like `Moved`).
+fn map_mouse_event(mouse_event: MouseEvent) -> Option<Event> {
+    let x = mouse_event.column as usize;
+    let y = mouse_event.row as usize;
+
+    match mouse_event.kind {
+        MouseEventKind::Down(button) => Some(Event::MouseDown(map_mouse_button(button), x, y)),
+        MouseEventKind::Up(button) => Some(Event::MouseUp(map_mouse_button(button), x, y)),
+        MouseEventKind::Drag(button) => Some(Event::MouseDrag(map_mouse_button(button), x, y)),
+        MouseEventKind::ScrollUp => Some(Event::Scroll(-1)),
+        MouseEventKind::ScrollDown => Some(Event::Scroll(1)),
+        _ => None,
+    }
+}
+
+fn map_mouse_button(button: CMouseButton) -> MouseButton {
+    match button {
+        CMouseButton::Left => MouseButton::Left,
+        CMouseButton::Right => MouseButton::Right,
+        CMouseButton::Middle => MouseButton::Middle,
+    }
+}
+
+/// Maps our backend-agnostic `Color` to what crossterm understands.
+fn to_crossterm_color(color: Color) -> CColor {
+    match color {
+        Color::Reset => CColor::Reset,
+        Color::Black => CColor::Black,
+        Color::White => CColor::White,
+        Color::LightGray => CColor::Grey,
+        Color::Blue => CColor::Blue,
+        Color::Rgb(r, g, b) => CColor::Rgb { r, g, b },
+        Color::Indexed(i) => CColor::AnsiValue(i),
+    }
+}
 
 pub trait TerminalInterface {
     /// Inits the terminal.
@@ -48,6 +131,34 @@ impl Terminal {
     }
 }
 
+impl EventSource for Terminal {
+    /// Non-blocking: maps whatever crossterm has pending to our events,
+    /// skipping the ones we don't model.
+    fn next_event(&mut self) -> Result<Option<Event>, EventsError> {
+        while event::poll(Duration::from_millis(0))? {
+            match event::read()? {
+                CEvent::Key(key_event) => {
+                    if let Some(key_press) = map_key_event(key_event) {
+                        return Ok(Some(Event::KeyPress(key_press)));
+                    }
+                }
+                CEvent::Resize(width, height) => {
+                    return Ok(Some(Event::Resize(width as usize, height as usize)))
+                }
+                CEvent::Mouse(mouse_event) => {
+                    if let Some(event) = map_mouse_event(mouse_event) {
+                        return Ok(Some(event));
+                    }
+                }
+                CEvent::Paste(text) => return Ok(Some(Event::Paste(text))),
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+}
+
 impl TerminalInterface for Terminal {
     fn queue(&self, command: TerminalCommand) -> Result<(), RendererError> {
         match command {
@@ -60,15 +171,33 @@ impl TerminalInterface for Terminal {
                 }
                 Ok(())
             }
+            TerminalCommand::PrintStyled(runs) => {
+                for (text, color) in runs {
+                    Self::queue_command(SetForegroundColor(to_crossterm_color(color)))?;
+                    Self::queue_command(Print(text))?;
+                }
+                // Don't let the last run's color leak into later prints.
+                Self::queue_command(ResetColor)
+            }
             TerminalCommand::MoveCursor(x, y) => Self::queue_command(MoveTo(x as u16, y as u16)),
             TerminalCommand::HideCursor => Self::queue_command(Hide),
             TerminalCommand::ShowCursor => Self::queue_command(Show),
-            TerminalCommand::ChangeCursorStyleBar => {
-                Self::queue_command(SetCursorStyle::BlinkingBar)
+            TerminalCommand::SetCursorShape(shape) => Self::queue_command(match shape {
+                // These map to the DECSCUSR blinking variants.
+                CursorShape::Block => SetCursorStyle::BlinkingBlock,
+                CursorShape::Bar => SetCursorStyle::BlinkingBar,
+                CursorShape::Underline => SetCursorStyle::BlinkingUnderScore,
+            }),
+            TerminalCommand::EnableReverseVideo => {
+                Self::queue_command(SetAttribute(Attribute::Reverse))
+            }
+            TerminalCommand::DisableReverseVideo => {
+                Self::queue_command(SetAttribute(Attribute::NoReverse))
             }
-            TerminalCommand::ChangeCursorStyleBlock => {
-                Self::queue_command(SetCursorStyle::BlinkingBlock)
+            TerminalCommand::SetForegroundColor(color) => {
+                Self::queue_command(SetForegroundColor(to_crossterm_color(color)))
             }
+            TerminalCommand::ResetColor => Self::queue_command(ResetColor),
         }
     }
 
@@ -83,7 +212,13 @@ impl TerminalInterface for Terminal {
 
         enable_raw_mode()
             .map_err(|e| RendererError::TerminalError(format!("Could not enter raw mode: {e}")))?;
-        execute!(stdout, EnterAlternateScreen).map_err(|e| {
+        execute!(
+            stdout,
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableBracketedPaste
+        )
+        .map_err(|e| {
             RendererError::TerminalError(format!("Could not enter alternate screen: {e}"))
         })?;
 
@@ -96,7 +231,13 @@ impl TerminalInterface for Terminal {
         disable_raw_mode().map_err(|e| {
             RendererError::TerminalError(format!("Could not disable raw mode: {e}"))
         })?;
-        execute!(stdout, LeaveAlternateScreen).map_err(|e| {
+        execute!(
+            stdout,
+            DisableBracketedPaste,
+            DisableMouseCapture,
+            LeaveAlternateScreen
+        )
+        .map_err(|e| {
             RendererError::TerminalError(format!("Could not leave alternate screen: {e}"))
         })?;
 
@@ -1,6 +1,6 @@
 use std::{
     fs::File,
-    io::{self, BufReader},
+    io::{self, BufReader, BufWriter, Write},
     path::Path,
 };
 
@@ -17,8 +17,26 @@ pub enum TextEngineError {
 
 pub use ropey::{Rope, RopeSlice};
 use thiserror::Error;
+use unicode_segmentation::UnicodeSegmentation;
 use utils::Position;
 
+/// The line ending styles a file can use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,   // Unix style: '\n'.
+    CrLf, // Windows style: '\r\n'.
+}
+
+impl LineEnding {
+    /// Returns the literal characters of this line ending.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
 /// This encapsulates `Rope` as the main data structure of the-editor, with some
 /// given modifications.
 pub struct TextEngine {
@@ -42,6 +60,23 @@ impl TextEngine {
         Ok(TextEngine { rope })
     }
 
+    /// Writes the `TextEngine` to a file, streaming the rope's chunks so
+    /// a multi-megabyte buffer never has to be collected into one `String`.
+    pub fn write_to<P>(&self, path: P) -> Result<(), TextEngineError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+
+        for chunk in self.rope.chunks() {
+            writer.write_all(chunk.as_bytes())?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
     /// Returns the length of the lines.
     pub fn len_lines(&self) -> usize {
         self.rope.len_lines()
@@ -114,6 +149,25 @@ impl TextEngine {
         }
     }
 
+    //
+    // UTF-16 offsets
+    //
+    // Platform text APIs (and LSP) count in UTF-16 code units, where an
+    // astral-plane character like an emoji is two units instead of one
+    // char. These convert between that world and our char indices.
+
+    /// Converts a char index into the equivalent UTF-16 code unit offset.
+    pub fn char_idx_to_utf16(&self, char_idx: usize) -> usize {
+        self.rope.char_to_utf16_cu(char_idx)
+    }
+
+    /// Converts a UTF-16 code unit offset back into a char index. An
+    /// offset landing inside a surrogate pair resolves to the character
+    /// containing it, so round-trips are safe across astral-plane text.
+    pub fn utf16_to_char_idx(&self, utf16_idx: usize) -> usize {
+        self.rope.utf16_cu_to_char(utf16_idx)
+    }
+
     //
     // Editing
     //
@@ -123,6 +177,11 @@ impl TextEngine {
         self.rope.insert_char(idx, c)
     }
 
+    /// Inserts a string at a given index.
+    pub fn insert(&mut self, idx: usize, text: &str) {
+        self.rope.insert(idx, text)
+    }
+
     /// Deletes a character before the given index (backspace).
     pub fn delete_char_backward(&mut self, idx: usize) {
         if idx == 0 {
@@ -138,4 +197,528 @@ impl TextEngine {
         }
         self.rope.remove(idx..idx + 1);
     }
+
+    /// Removes every character in `start..end` (end exclusive).
+    pub fn remove_range(&mut self, start: usize, end: usize) {
+        let end = end.min(self.rope.len_chars());
+        if start >= end {
+            return;
+        }
+
+        self.rope.remove(start..end);
+    }
+
+    //
+    // Line endings
+    //
+
+    /// Detects the dominant line ending of the buffer by counting every
+    /// line break. Ties (and buffers without breaks) fall back to `Lf`.
+    pub fn detect_line_ending(&self) -> LineEnding {
+        let mut lf = 0;
+        let mut crlf = 0;
+        let mut prev = '\0';
+
+        for c in self.rope.chars() {
+            if c == '\n' {
+                if prev == '\r' {
+                    crlf += 1;
+                } else {
+                    lf += 1;
+                }
+            }
+            prev = c;
+        }
+
+        if crlf > lf {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    /// Rewrites every line break to the given style, so saving does not
+    /// produce files with mixed endings.
+    pub fn normalize_line_endings(&mut self, to: LineEnding) {
+        let text = self.rope.to_string();
+        let normalized = text.replace("\r\n", "\n");
+
+        let result = match to {
+            LineEnding::Lf => normalized,
+            LineEnding::CrLf => normalized.replace('\n', "\r\n"),
+        };
+
+        self.rope = Rope::from(result);
+    }
+
+    //
+    // Graphemes
+    //
+
+    /// Returns the char index of the next grapheme cluster boundary after
+    /// `char_idx`, so the cursor never lands inside an emoji or a
+    /// combining sequence.
+    pub fn next_grapheme_boundary(&self, char_idx: usize) -> usize {
+        let total = self.len_chars();
+        if char_idx >= total {
+            return total;
+        }
+
+        let line_idx = self.rope.char_to_line(char_idx);
+        let line_start = self.rope.line_to_char(line_idx);
+        let offset = char_idx - line_start;
+
+        self.grapheme_boundaries_of_line(line_idx)
+            .into_iter()
+            .find(|&boundary| boundary > offset)
+            .map(|boundary| line_start + boundary)
+            .unwrap_or(total)
+    }
+
+    /// Returns the char index of the previous grapheme cluster boundary
+    /// before `char_idx`.
+    pub fn prev_grapheme_boundary(&self, char_idx: usize) -> usize {
+        if char_idx == 0 {
+            return 0;
+        }
+
+        let char_idx = char_idx.min(self.len_chars());
+        let line_idx = self.rope.char_to_line(char_idx);
+        let line_start = self.rope.line_to_char(line_idx);
+        let offset = char_idx - line_start;
+
+        if offset == 0 {
+            // At the start of a line: the previous boundary is the last
+            // grapheme start of the line above.
+            let prev_line_idx = line_idx - 1;
+            let prev_line_start = self.rope.line_to_char(prev_line_idx);
+            let boundaries = self.grapheme_boundaries_of_line(prev_line_idx);
+
+            // The last entry is the line end; the one before it is the
+            // start of the last grapheme.
+            return prev_line_start + boundaries[boundaries.len().saturating_sub(2)];
+        }
+
+        self.grapheme_boundaries_of_line(line_idx)
+            .into_iter()
+            .rev()
+            .find(|&boundary| boundary < offset)
+            .map(|boundary| line_start + boundary)
+            .unwrap_or(line_start)
+    }
+
+    /// Returns the char index of the grapheme cluster start at or before
+    /// `char_idx`, i.e. `char_idx` itself when it is already a boundary.
+    pub fn grapheme_start(&self, char_idx: usize) -> usize {
+        let total = self.len_chars();
+        if char_idx >= total {
+            return total;
+        }
+
+        let line_idx = self.rope.char_to_line(char_idx);
+        let line_start = self.rope.line_to_char(line_idx);
+        let offset = char_idx - line_start;
+
+        self.grapheme_boundaries_of_line(line_idx)
+            .into_iter()
+            .rev()
+            .find(|&boundary| boundary <= offset)
+            .map(|boundary| line_start + boundary)
+            .unwrap_or(line_start)
+    }
+
+    /// Returns the char offsets (within the line) of every grapheme start,
+    /// plus one final entry for the line end.
+    fn grapheme_boundaries_of_line(&self, line_idx: usize) -> Vec<usize> {
+        let line = self.rope.line(line_idx).to_string();
+        let mut boundaries = Vec::new();
+        let mut char_offset = 0;
+
+        for grapheme in line.graphemes(true) {
+            boundaries.push(char_offset);
+            char_offset += grapheme.chars().count();
+        }
+
+        boundaries.push(char_offset);
+        boundaries
+    }
+
+    //
+    // Searching
+    //
+
+    /// Finds the first occurrence of `pattern` at or after `from`,
+    /// returning the char index of the match start.
+    pub fn search_forward(&self, from: usize, pattern: &str) -> Option<usize> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let total = self.len_chars();
+
+        if pattern.is_empty() || pattern.len() > total {
+            return None;
+        }
+
+        (from..=total - pattern.len()).find(|&start| self.matches_at(start, &pattern))
+    }
+
+    /// Finds the last occurrence of `pattern` strictly before `before`,
+    /// returning the char index of the match start.
+    pub fn search_backward(&self, before: usize, pattern: &str) -> Option<usize> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let total = self.len_chars();
+
+        if pattern.is_empty() || pattern.len() > total {
+            return None;
+        }
+
+        let last_start = (total - pattern.len()).min(before.saturating_sub(1));
+        (0..=last_start)
+            .rev()
+            .find(|&start| self.matches_at(start, &pattern))
+    }
+
+    /// Returns the char-index ranges (end exclusive) of every
+    /// non-overlapping occurrence of `pattern`. An empty pattern matches
+    /// nothing.
+    pub fn find_all(&self, pattern: &str) -> Vec<(usize, usize)> {
+        let pattern_len = pattern.chars().count();
+        let mut matches = Vec::new();
+        let mut from = 0;
+
+        while let Some(start) = self.search_forward(from, pattern) {
+            matches.push((start, start + pattern_len));
+            from = start + pattern_len;
+        }
+
+        matches
+    }
+
+    /// Finds the char index of the bracket matching the one at `char_idx`,
+    /// counting nesting depth along the way. Returns `None` when the char
+    /// is not a bracket or its pair is unbalanced.
+    pub fn find_matching_bracket(&self, char_idx: usize) -> Option<usize> {
+        if char_idx >= self.len_chars() {
+            return None;
+        }
+
+        let (open, close, forward) = match self.char(char_idx) {
+            '(' => ('(', ')', true),
+            '[' => ('[', ']', true),
+            '{' => ('{', '}', true),
+            ')' => ('(', ')', false),
+            ']' => ('[', ']', false),
+            '}' => ('{', '}', false),
+            _ => return None,
+        };
+
+        let mut depth = 0usize;
+        if forward {
+            for i in char_idx + 1..self.len_chars() {
+                let c = self.char(i);
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                    depth -= 1;
+                }
+            }
+        } else {
+            for i in (0..char_idx).rev() {
+                let c = self.char(i);
+                if c == close {
+                    depth += 1;
+                } else if c == open {
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                    depth -= 1;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Replaces the text in `start..end` (end exclusive) with another string.
+    pub fn replace_range(&mut self, start: usize, end: usize, with: &str) {
+        self.remove_range(start, end);
+        self.rope.insert(start.min(self.rope.len_chars()), with);
+    }
+
+    /// Replaces every occurrence of `pattern`, returning how many
+    /// replacements were made. This is what backs `:s/foo/bar/g`.
+    pub fn replace_all(&mut self, pattern: &str, with: &str) -> usize {
+        let matches = self.find_all(pattern);
+
+        // Walk backwards so earlier ranges stay valid while replacing.
+        for &(start, end) in matches.iter().rev() {
+            self.replace_range(start, end, with);
+        }
+
+        matches.len()
+    }
+
+    /// Returns the char-index ranges (end exclusive) of every regex match.
+    /// Matching runs line by line over the rope, so the whole buffer is
+    /// never collected into one `String`; patterns cannot span lines.
+    #[cfg(feature = "regex")]
+    pub fn find_regex(&self, pattern: &str) -> Result<Vec<(usize, usize)>, regex::Error> {
+        let regex = regex::Regex::new(pattern)?;
+        let mut matches = Vec::new();
+        let mut line_start = 0;
+
+        for line in self.rope.lines() {
+            let text = line.to_string();
+            // Match against the line without its terminator, so `$`
+            // anchors to the end of the content rather than never
+            // matching before the `\n`.
+            let content = text.strip_suffix('\n').unwrap_or(&text);
+            let content = content.strip_suffix('\r').unwrap_or(content);
+
+            for found in regex.find_iter(content) {
+                // The regex reports byte offsets, our APIs speak chars.
+                let start = line_start + content[..found.start()].chars().count();
+                let end = line_start + content[..found.end()].chars().count();
+                matches.push((start, end));
+            }
+
+            line_start += line.len_chars();
+        }
+
+        Ok(matches)
+    }
+
+    /// Returns whether `pattern` occurs at exactly `idx`.
+    fn matches_at(&self, idx: usize, pattern: &[char]) -> bool {
+        pattern
+            .iter()
+            .enumerate()
+            .all(|(i, &c)| self.rope.char(idx + i) == c)
+    }
+
+    /// Returns the text in `start..end` (end exclusive) as a `String`.
+    pub fn slice_to_string(&self, start: usize, end: usize) -> String {
+        let end = end.min(self.rope.len_chars());
+        if start >= end {
+            return String::new();
+        }
+
+        self.rope.slice(start..end).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(text: &str) -> TextEngine {
+        let mut engine = TextEngine::new();
+        engine.insert(0, text);
+        engine
+    }
+
+    #[test]
+    fn grapheme_boundaries_skip_combining_marks() {
+        // "e" followed by a combining acute accent is one grapheme of two
+        // chars; the boundaries must jump over the mark.
+        let engine = engine("e\u{301}x");
+
+        assert_eq!(engine.next_grapheme_boundary(0), 2);
+        assert_eq!(engine.next_grapheme_boundary(2), 3);
+        assert_eq!(engine.prev_grapheme_boundary(2), 0);
+        assert_eq!(engine.prev_grapheme_boundary(3), 2);
+    }
+
+    #[test]
+    fn grapheme_boundaries_cross_line_breaks() {
+        let engine = engine("ab\ncd");
+
+        // From the start of the second line, the previous boundary is the
+        // start of the newline that ends the first.
+        assert_eq!(engine.prev_grapheme_boundary(3), 2);
+        assert_eq!(engine.next_grapheme_boundary(2), 3);
+    }
+
+    #[test]
+    fn grapheme_start_snaps_into_the_cluster() {
+        let engine = engine("e\u{301}x");
+
+        // An index inside the cluster resolves to the cluster's start; an
+        // index already on a boundary stays put.
+        assert_eq!(engine.grapheme_start(1), 0);
+        assert_eq!(engine.grapheme_start(2), 2);
+        assert_eq!(engine.grapheme_start(10), engine.len_chars());
+    }
+
+    #[test]
+    fn grapheme_boundaries_clamp_at_the_ends() {
+        let engine = engine("ab");
+
+        assert_eq!(engine.prev_grapheme_boundary(0), 0);
+        assert_eq!(engine.next_grapheme_boundary(2), 2);
+        assert_eq!(engine.next_grapheme_boundary(10), 2);
+    }
+
+    #[test]
+    fn search_finds_matches_in_both_directions() {
+        let engine = engine("foo bar foo");
+
+        assert_eq!(engine.search_forward(0, "foo"), Some(0));
+        assert_eq!(engine.search_forward(1, "foo"), Some(8));
+        assert_eq!(engine.search_forward(9, "foo"), None);
+
+        assert_eq!(engine.search_backward(11, "foo"), Some(8));
+        assert_eq!(engine.search_backward(8, "foo"), Some(0));
+        assert_eq!(engine.search_forward(0, ""), None);
+    }
+
+    #[test]
+    fn find_all_returns_non_overlapping_ranges() {
+        assert_eq!(
+            engine("ababab").find_all("ab"),
+            vec![(0, 2), (2, 4), (4, 6)]
+        );
+        // "aa" in "aaa" must not double-count the middle character.
+        assert_eq!(engine("aaa").find_all("aa"), vec![(0, 2)]);
+    }
+
+    #[test]
+    fn replace_range_swaps_text_of_a_different_length() {
+        let mut engine = engine("hello world");
+
+        engine.replace_range(0, 5, "goodbye");
+        assert_eq!(engine.slice_to_string(0, engine.len_chars()), "goodbye world");
+    }
+
+    #[test]
+    fn replace_all_counts_its_replacements() {
+        let mut engine = engine("foo bar foo");
+
+        assert_eq!(engine.replace_all("foo", "quux"), 2);
+        assert_eq!(
+            engine.slice_to_string(0, engine.len_chars()),
+            "quux bar quux"
+        );
+        assert_eq!(engine.replace_all("missing", "x"), 0);
+    }
+
+    #[test]
+    fn detect_line_ending_picks_the_dominant_style() {
+        assert_eq!(engine("a\nb\n").detect_line_ending(), LineEnding::Lf);
+        assert_eq!(engine("a\r\nb\r\n").detect_line_ending(), LineEnding::CrLf);
+        // A mixed file with a tie (and an empty one) falls back to Lf.
+        assert_eq!(engine("a\r\nb\n").detect_line_ending(), LineEnding::Lf);
+        assert_eq!(engine("").detect_line_ending(), LineEnding::Lf);
+    }
+
+    #[test]
+    fn normalize_line_endings_rewrites_every_break() {
+        let mut engine = engine("a\r\nb\nc\r\n");
+
+        engine.normalize_line_endings(LineEnding::Lf);
+        assert_eq!(engine.slice_to_string(0, engine.len_chars()), "a\nb\nc\n");
+
+        engine.normalize_line_endings(LineEnding::CrLf);
+        assert_eq!(
+            engine.slice_to_string(0, engine.len_chars()),
+            "a\r\nb\r\nc\r\n"
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn find_regex_reports_char_ranges_per_line() {
+        let engine = engine("ab12\ncd34");
+
+        let matches = engine.find_regex(r"\d+").expect("a valid pattern");
+        assert_eq!(matches, vec![(2, 4), (7, 9)]);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn find_regex_anchors_ignore_line_terminators() {
+        // `$` must anchor to the line content, not fail on the `\n`.
+        let engine = engine("foo\nbarfoo\nfoo bar\n");
+
+        // "barfoo" ends in `foo`, "foo bar" does not.
+        let matches = engine.find_regex("foo$").expect("a valid pattern");
+        assert_eq!(matches, vec![(0, 3), (7, 10)]);
+
+        let matches = engine.find_regex("^foo").expect("a valid pattern");
+        assert_eq!(matches, vec![(0, 3), (11, 14)]);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn find_regex_handles_crlf_and_bad_patterns() {
+        let crlf = engine("foo\r\nfoo\r\n");
+        let matches = crlf.find_regex("foo$").expect("a valid pattern");
+        assert_eq!(matches, vec![(0, 3), (5, 8)]);
+
+        assert!(crlf.find_regex("(").is_err());
+    }
+
+    #[test]
+    fn matching_brackets_respect_nesting() {
+        let mixed = engine("(a[b]c)");
+
+        assert_eq!(mixed.find_matching_bracket(0), Some(6));
+        assert_eq!(mixed.find_matching_bracket(6), Some(0));
+        assert_eq!(mixed.find_matching_bracket(2), Some(4));
+        assert_eq!(mixed.find_matching_bracket(4), Some(2));
+
+        // A nested pair of the same kind is skipped over.
+        let nested = engine("{{}}");
+        assert_eq!(nested.find_matching_bracket(0), Some(3));
+        assert_eq!(nested.find_matching_bracket(3), Some(0));
+    }
+
+    #[test]
+    fn non_brackets_and_unbalanced_pairs_do_not_match() {
+        assert_eq!(engine("abc").find_matching_bracket(1), None);
+        assert_eq!(engine("(()").find_matching_bracket(0), None);
+        assert_eq!(engine("())").find_matching_bracket(2), None);
+        assert_eq!(engine("()").find_matching_bracket(10), None);
+    }
+
+    #[test]
+    fn utf16_offsets_count_surrogate_pairs() {
+        // The emoji is one char but two UTF-16 code units.
+        let engine = engine("a\u{1F44D}b");
+
+        assert_eq!(engine.char_idx_to_utf16(0), 0);
+        assert_eq!(engine.char_idx_to_utf16(1), 1);
+        assert_eq!(engine.char_idx_to_utf16(2), 3);
+        assert_eq!(engine.char_idx_to_utf16(3), 4);
+
+        assert_eq!(engine.utf16_to_char_idx(3), 2);
+        // An offset inside the surrogate pair resolves to the character
+        // containing it instead of panicking.
+        assert_eq!(engine.utf16_to_char_idx(2), 1);
+    }
+
+    #[test]
+    fn utf16_round_trip_is_stable_for_ascii() {
+        let engine = engine("plain text");
+
+        for idx in 0..=engine.len_chars() {
+            assert_eq!(engine.utf16_to_char_idx(engine.char_idx_to_utf16(idx)), idx);
+        }
+    }
+
+    #[test]
+    fn write_to_round_trips_through_a_file() {
+        let text = "first line\nsecond line\n";
+        let path = std::env::temp_dir().join(format!(
+            "the-editor-write-test-{}.txt",
+            std::process::id()
+        ));
+
+        engine(text).write_to(&path).expect("to write the file");
+        let reloaded = TextEngine::from_file(&path).expect("to read the file back");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(reloaded.slice_to_string(0, reloaded.len_chars()), text);
+    }
 }
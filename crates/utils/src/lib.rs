@@ -26,10 +26,13 @@ pub fn init_logging() -> Result<(), Box<dyn std::error::Error>> {
 pub use log::{debug, error, info, warn};
 
 /// Just like vim.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Mode {
     Normal,
     Insert,
+    Visual,
+    Search,
+    Command,
 }
 
 /// NOTE: Maybe I'll split this into multiple different commands.
@@ -53,6 +56,55 @@ pub enum Command {
     MoveCursorWordForward(bool), // bool indicates if the word is big or not.
     MoveCursorWordBackward(bool),
     MoveCursorWordForwardEnd(bool),
+    DeleteSelection,
+    YankSelection,
+    Paste(bool), // `p`/`P`: puts the register after (true) or before the cursor.
+    Undo,
+    Redo,
+    StartSearch(bool), // bool indicates if the search goes forward.
+    SearchInput(char),
+    SearchBackspace,
+    SearchSubmit,
+    SearchCancel,
+    SearchForward(String),
+    SearchBackward(String),
+    SearchNext,
+    SearchPrev,
+    Save,
+    SaveAs(String),
+    StartCommandLine,
+    CommandInput(char),
+    CommandBackspace,
+    CommandSubmit,
+    CommandCancel,
+    GotoLine(usize), // 1-based, like vim's :<number>.
+    GotoLastLine,    // `G`, or jumps to the pending count's line (`10G`).
+    CountDigit(usize),
+    RepeatLastChange, // `.`
+    SetCursorAtScreen(usize, usize), // Mouse click: a viewport cell the cursor should jump to.
+    ScrollUp(usize),                 // Scroll the view up by n lines (mouse wheel).
+    ScrollDown(usize),
+    HalfPageDown, // `Ctrl-d`: cursor and view move half a screen down.
+    HalfPageUp,   // `Ctrl-u`.
+    PageDown, // `Ctrl-f`: cursor and view move a full screen down.
+    PageUp,   // `Ctrl-b`.
+    CenterView, // `zz`: scrolls so the cursor line sits mid-screen.
+    ViewTop,    // `zt`.
+    ViewBottom, // `zb`.
+    InsertText(String), // A whole pasted block, inserted in one edit.
+    TransformCase(CaseTransform), // Changes the case of the selection.
+    SurroundSelection(char), // Wraps the selection in a matching pair.
+    GotoMatchingBracket,     // `%`: jumps to the matching bracket.
+    IncrementNumber(i64),    // `Ctrl-a`/`Ctrl-x`: adds to the number at the cursor.
+    JumpBack,    // `Ctrl-o`: returns to the position before the last jump.
+    JumpForward, // `Ctrl-i`.
+    GotoLastChange, // `;`: moves to where an older edit happened.
+    GotoNextChange, // `,`.
+    IndentSelection,  // `>`: indents the selected lines by one level.
+    OutdentSelection, // `<`.
+    JoinLines,     // `J`: joins the current line with the next.
+    OpenLineBelow, // `o`: new line below, keeping indent, insert mode.
+    OpenLineAbove, // `O`.
 }
 
 /// Position determines any (x, y) point in the plane.
@@ -91,7 +143,7 @@ impl Cursor {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum CharClass {
     Whitespace,
     Word,
@@ -110,6 +162,98 @@ pub fn get_char_class(c: char, big_word: bool) -> CharClass {
     }
 }
 
+/// Returns whether a word boundary sits between two adjacent characters,
+/// i.e. they belong to different character classes.
+pub fn is_word_boundary(prev: char, next: char, big_word: bool) -> bool {
+    get_char_class(prev, big_word) != get_char_class(next, big_word)
+}
+
+/// Returns the char ranges (end exclusive) of every word in a line, where
+/// a word is a run of characters of the same non-whitespace class. With
+/// `big_word`, punctuation runs collapse into the surrounding word.
+pub fn word_ranges(line: &str, big_word: bool) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut prev_class = CharClass::Whitespace;
+    let mut len = 0;
+
+    for (i, c) in line.chars().enumerate() {
+        let class = get_char_class(c, big_word);
+
+        if class == CharClass::Whitespace {
+            if let Some(s) = start.take() {
+                ranges.push((s, i));
+            }
+        } else if start.is_none() || class != prev_class {
+            // Either a new word, or the boundary between e.g. a word run
+            // and a punctuation run.
+            if let Some(s) = start.take() {
+                ranges.push((s, i));
+            }
+            start = Some(i);
+        }
+
+        prev_class = class;
+        len = i + 1;
+    }
+
+    if let Some(s) = start {
+        ranges.push((s, len));
+    }
+
+    ranges
+}
+
+/// How a case transform changes the selected text.
+#[derive(Clone, Copy)]
+pub enum CaseTransform {
+    Upper,
+    Lower,
+    Toggle, // Swap the case of every cased character.
+    Title,  // Uppercase the first letter of each word, lowercase the rest.
+}
+
+/// Applies a `CaseTransform` to a string, leaving uncased characters
+/// untouched.
+pub fn transform_case(text: &str, transform: CaseTransform) -> String {
+    match transform {
+        CaseTransform::Upper => text.to_uppercase(),
+        CaseTransform::Lower => text.to_lowercase(),
+        CaseTransform::Toggle => text
+            .chars()
+            .map(|c| {
+                if c.is_uppercase() {
+                    c.to_lowercase().to_string()
+                } else if c.is_lowercase() {
+                    c.to_uppercase().to_string()
+                } else {
+                    c.to_string()
+                }
+            })
+            .collect(),
+        CaseTransform::Title => {
+            let mut result = String::with_capacity(text.len());
+            let mut at_word_start = true;
+
+            for c in text.chars() {
+                if c.is_alphanumeric() {
+                    if at_word_start {
+                        result.extend(c.to_uppercase());
+                    } else {
+                        result.extend(c.to_lowercase());
+                    }
+                    at_word_start = false;
+                } else {
+                    result.push(c);
+                    at_word_start = true;
+                }
+            }
+
+            result
+        }
+    }
+}
+
 /// Builds the welcome message.
 pub fn build_welcome_message(width: usize) -> String {
     if width == 0 {
@@ -128,3 +272,71 @@ pub fn build_welcome_message(width: usize) -> String {
     full_message.truncate(width);
     return full_message;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn char_classes_split_words_and_punctuation() {
+        assert!(get_char_class('a', false) == CharClass::Word);
+        assert!(get_char_class('_', false) == CharClass::Word);
+        assert!(get_char_class('.', false) == CharClass::Punctuation);
+        assert!(get_char_class(' ', false) == CharClass::Whitespace);
+
+        // A big word swallows punctuation: only whitespace splits.
+        assert!(get_char_class('.', true) == CharClass::Word);
+    }
+
+    #[test]
+    fn word_boundaries_sit_between_classes() {
+        assert!(is_word_boundary('a', '.', false));
+        assert!(is_word_boundary('.', ' ', false));
+        assert!(!is_word_boundary('a', 'b', false));
+        assert!(!is_word_boundary('a', '.', true));
+    }
+
+    #[test]
+    fn word_ranges_split_on_whitespace() {
+        assert_eq!(word_ranges("foo bar", false), vec![(0, 3), (4, 7)]);
+        assert_eq!(word_ranges("  foo  ", false), vec![(2, 5)]);
+        assert_eq!(word_ranges("", false), Vec::new());
+    }
+
+    #[test]
+    fn word_ranges_split_punctuation_runs() {
+        // `foo.bar` is three small words, but one big word.
+        assert_eq!(
+            word_ranges("foo.bar", false),
+            vec![(0, 3), (3, 4), (4, 7)]
+        );
+        assert_eq!(word_ranges("foo.bar", true), vec![(0, 7)]);
+    }
+
+    #[test]
+    fn upper_and_lower_transforms() {
+        assert_eq!(transform_case("aBc 1!", CaseTransform::Upper), "ABC 1!");
+        assert_eq!(transform_case("aBc 1!", CaseTransform::Lower), "abc 1!");
+    }
+
+    #[test]
+    fn toggle_swaps_only_cased_characters() {
+        assert_eq!(transform_case("aBc 1!", CaseTransform::Toggle), "AbC 1!");
+        // Toggling twice gets back to the original.
+        let toggled = transform_case("Hello, World", CaseTransform::Toggle);
+        assert_eq!(
+            transform_case(&toggled, CaseTransform::Toggle),
+            "Hello, World"
+        );
+    }
+
+    #[test]
+    fn title_capitalizes_each_word() {
+        assert_eq!(
+            transform_case("heLLo woRLd", CaseTransform::Title),
+            "Hello World"
+        );
+        // Any uncased character restarts a word.
+        assert_eq!(transform_case("foo-bar", CaseTransform::Title), "Foo-Bar");
+    }
+}